@@ -0,0 +1,378 @@
+// front-end for ABNF of RFC 5234, with the case sensitive strings of RFC 7405
+use crate::front::*;
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+// core rules of RFC 5234 appendix B.1, added when a grammar uses them without defining them
+const CORE_RULES: &str = r#"
+ALPHA = %x41-5A / %x61-7A
+BIT = "0" / "1"
+CHAR = %x01-7F
+CR = %x0D
+CRLF = CR LF
+CTL = %x00-1F / %x7F
+DIGIT = %x30-39
+DQUOTE = %x22
+HEXDIG = DIGIT / "A" / "B" / "C" / "D" / "E" / "F"
+HTAB = %x09
+LF = %x0A
+LWSP = *(WSP / CRLF WSP)
+OCTET = %x00-FF
+SP = %x20
+VCHAR = %x21-7E
+WSP = SP / HTAB
+"#;
+
+struct AbnfParser<'a> {
+    s: Scanner<'a>,
+    alloc: &'a ASTAlloc<'a>,
+    errors: Errors,
+    // names of rules left out because of syntax errors after their name
+    broken: Vec<&'a str>,
+    // rule names are case insensitive, each is spelled like its first occurrence
+    names: BTreeMap<String, &'a str>,
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-'
+}
+
+impl<'a> AbnfParser<'a> {
+    // skip spaces and comments
+    fn gap(&mut self) {
+        loop {
+            self.s.take_while(char::is_whitespace);
+            if !self.s.eat(";") {
+                break;
+            }
+            self.s.take_while(|ch| ch != '\n');
+        }
+    }
+
+    // whether a rule starts at the cursor
+    fn at_rule(&self) -> bool {
+        let rest = self.s.rest();
+        let after = rest.trim_start_matches(is_name_char);
+        rest.starts_with(|ch: char| ch.is_ascii_alphabetic())
+            && after.trim_start_matches([' ', '\t']).starts_with('=')
+    }
+
+    fn name(&mut self) -> &'a str {
+        let name = self.s.take_while(is_name_char);
+        self.names.entry(name.to_ascii_lowercase()).or_insert(name)
+    }
+
+    // a rule, and whether it adds alternatives to an earlier one with =/
+    fn rule(&mut self) -> Option<(RuleDef<'a>, bool)> {
+        let lo = self.s.pos;
+        if !self.s.peek().is_some_and(|ch| ch.is_ascii_alphabetic()) {
+            self.s.error(&mut self.errors, &["rule name"]);
+            return None;
+        }
+        let name = self.name();
+        // popped once the rule parses
+        self.broken.push(name);
+        self.gap();
+        let incremental = self.s.eat("=/");
+        if !incremental && !self.s.eat("=") {
+            self.s.error(&mut self.errors, &["`=`", "`=/`"]);
+            return None;
+        }
+        let prod = self.alternation()?;
+        self.gap();
+        if !(self.s.peek().is_none() || self.at_rule()) {
+            self.s.error(&mut self.errors, &["`/`", "rule"]);
+            return None;
+        }
+        self.broken.pop();
+        let hi = prod.last().unwrap().span().hi.max(lo);
+        let rule = RuleDef {
+            name,
+            span: self.s.span(lo, hi),
+            prod,
+        };
+        Some((rule, incremental))
+    }
+
+    // concatenations separated by /
+    fn alternation(&mut self) -> Option<Vec<&'a Prod<'a>>> {
+        let mut res = vec![self.concatenation()?];
+        while self.s.eat("/") {
+            res.push(self.concatenation()?);
+        }
+        Some(res)
+    }
+
+    fn concatenation(&mut self) -> Option<&'a Prod<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        let mut items = vec![self.repetition()?];
+        loop {
+            self.gap();
+            match self.s.peek() {
+                None | Some('/') | Some(')') | Some(']') => break,
+                _ if self.at_rule() => break,
+                _ => items.push(self.repetition()?),
+            }
+        }
+        Some(make_concat(self.alloc, items, self.s.span(lo, lo)))
+    }
+
+    // element with an optional n, n* , *m or n*m prefix
+    fn repetition(&mut self) -> Option<&'a Prod<'a>> {
+        let lo = self.s.pos;
+        let min = self.s.count(&mut self.errors)?;
+        let (min, max) = if self.s.eat("*") {
+            (min.unwrap_or(0), self.s.count(&mut self.errors)?)
+        } else if let Some(count) = min {
+            (count, Some(count))
+        } else {
+            return self.element();
+        };
+        let element = self.element()?;
+        let span = self.s.span(lo, self.s.pos);
+        Some(make_repeat(self.alloc, element, min, max, span))
+    }
+
+    fn element(&mut self) -> Option<&'a Prod<'a>> {
+        let lo = self.s.pos;
+        match self.s.peek() {
+            Some(open @ '(') | Some(open @ '[') => {
+                self.s.bump();
+                let alts = self.alternation()?;
+                self.gap();
+                let close = if open == '(' { ")" } else { "]" };
+                if !self.s.eat(close) {
+                    self.s
+                        .error(&mut self.errors, &[&format!("`{}`", close), "`/`"]);
+                    return None;
+                }
+                let span = self.s.span(lo, self.s.pos);
+                let inner = make_alt(self.alloc, alts, span);
+                Some(if open == '[' {
+                    self.alloc.prod.alloc(Prod::Optional(inner, span))
+                } else {
+                    inner
+                })
+            }
+            Some('%') if self.s.eat("%s\"") => {
+                self.s.pos -= 1;
+                self.string(lo, '"', false)
+            }
+            Some('%') if self.s.eat("%i\"") => {
+                self.s.pos -= 1;
+                self.string(lo, '"', true)
+            }
+            Some('%') => {
+                self.s.bump();
+                self.num_val(lo)
+            }
+            Some('"') => self.string(lo, '"', true),
+            // prose describing what can't be written in ABNF
+            Some('<') => self.string(lo, '>', false),
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                let name = self.name();
+                let span = self.s.span(lo, self.s.pos);
+                Some(self.alloc.prod.alloc(Prod::NonTerminal(name, span)))
+            }
+            _ => {
+                self.s.error(
+                    &mut self.errors,
+                    &["rule name", "string", "`%`", "`(`", "`[`", "`<`"],
+                );
+                None
+            }
+        }
+    }
+
+    // terminal from lo to the closing delimiter, the cursor is at the opening one
+    // in a case insensitive string each letter is a class of both its cases,
+    // between the runs of other characters
+    fn string(&mut self, lo: usize, close: char, insensitive: bool) -> Option<&'a Prod<'a>> {
+        let open = self.s.bump().unwrap();
+        let start = self.s.pos;
+        let value = self.s.take_while(|ch| ch != close);
+        if self.s.bump().is_none() {
+            return self
+                .errors
+                .issue(self.s.loc(lo), ErrorKind::UnrecognizedChar(open));
+        }
+        if !insensitive || !value.contains(|ch: char| ch.is_ascii_alphabetic()) {
+            let raw = &self.s.code[lo..self.s.pos];
+            let span = self.s.span(lo, self.s.pos);
+            return Some(
+                self.alloc
+                    .prod
+                    .alloc(Prod::Terminal(Literal { raw, value }, span)),
+            );
+        }
+        let mut pieces = vec![];
+        let mut run = start;
+        for (i, ch) in value.char_indices() {
+            if !ch.is_ascii_alphabetic() {
+                continue;
+            }
+            let at = start + i;
+            if run < at {
+                pieces.push(self.piece(run, at));
+            }
+            let (lower, upper) = (ch.to_ascii_lowercase(), ch.to_ascii_uppercase());
+            let class = CharClass {
+                raw: self.alloc.string.alloc(format!("[{}{}]", lower, upper)),
+                negated: false,
+                ranges: vec![(upper, upper), (lower, lower)],
+            };
+            let span = self.s.span(at, at + 1);
+            pieces.push(self.alloc.prod.alloc(Prod::Class(class, span)));
+            run = at + 1;
+        }
+        let end = start + value.len();
+        if run < end {
+            pieces.push(self.piece(run, end));
+        }
+        Some(make_concat(self.alloc, pieces, self.s.span(lo, lo)))
+    }
+
+    // terminal for the characters from lo to hi of a string
+    fn piece(&self, lo: usize, hi: usize) -> &'a Prod<'a> {
+        let value = &self.s.code[lo..hi];
+        let raw = self.alloc.string.alloc(format!("\"{}\"", value));
+        let span = self.s.span(lo, hi);
+        self.alloc
+            .prod
+            .alloc(Prod::Terminal(Literal { raw, value }, span))
+    }
+
+    fn num(&mut self, radix: u32) -> Option<char> {
+        let digits = self.s.take_while(|ch| ch.is_digit(radix));
+        let ch = u32::from_str_radix(digits, radix)
+            .ok()
+            .and_then(std::char::from_u32);
+        if ch.is_none() {
+            self.s.error(&mut self.errors, &["code point"]);
+        }
+        ch
+    }
+
+    // %x41 character, %x41.42 string or %x41-5A range, after the %
+    fn num_val(&mut self, lo: usize) -> Option<&'a Prod<'a>> {
+        let radix = match self.s.peek().map(|ch| ch.to_ascii_lowercase()) {
+            Some('b') => 2,
+            Some('d') => 10,
+            Some('x') => 16,
+            _ => {
+                self.s.error(&mut self.errors, &["`b`", "`d`", "`x`"]);
+                return None;
+            }
+        };
+        self.s.bump();
+        let first = self.num(radix)?;
+        if self.s.eat("-") {
+            let last = self.num(radix)?;
+            let raw = &self.s.code[lo..self.s.pos];
+            let class = CharClass {
+                raw,
+                negated: false,
+                ranges: vec![(first, last)],
+            };
+            let span = self.s.span(lo, self.s.pos);
+            return Some(self.alloc.prod.alloc(Prod::Class(class, span)));
+        }
+        let mut value = first.to_string();
+        while self.s.eat(".") {
+            value.push(self.num(radix)?);
+        }
+        let raw = &self.s.code[lo..self.s.pos];
+        let value = self.alloc.string.alloc(value);
+        let span = self.s.span(lo, self.s.pos);
+        Some(
+            self.alloc
+                .prod
+                .alloc(Prod::Terminal(Literal { raw, value }, span)),
+        )
+    }
+
+    // skip to the start of the next rule
+    fn recover(&mut self) {
+        loop {
+            self.gap();
+            match self.s.peek() {
+                None => return,
+                _ if self.at_rule() => return,
+                Some(quote @ '"') => {
+                    self.s.bump();
+                    self.s.take_while(|ch| ch != quote);
+                    self.s.bump();
+                }
+                _ => {
+                    self.s.bump();
+                }
+            }
+        }
+    }
+
+    fn file(&mut self) -> Vec<RuleDef<'a>> {
+        let mut rules: Vec<RuleDef<'a>> = vec![];
+        loop {
+            self.gap();
+            if self.s.peek().is_none() {
+                break;
+            }
+            match self.rule() {
+                Some((rule, true)) => match rules.iter_mut().find(|r| r.name == rule.name) {
+                    Some(prev) => prev.prod.extend(rule.prod),
+                    None => rules.push(rule),
+                },
+                Some((rule, false)) => rules.push(rule),
+                None => self.recover(),
+            }
+        }
+        if rules.is_empty() && self.errors.0.is_empty() {
+            self.s.error(&mut self.errors, &["rule name"]);
+        }
+        rules
+    }
+}
+
+pub fn parse_abnf<'a>(code: &'a str, alloc: &'a ASTAlloc<'a>) -> (&'a File<'a>, Errors) {
+    let mut parser = AbnfParser {
+        s: Scanner::new(code),
+        alloc,
+        errors: Errors::default(),
+        broken: vec![],
+        names: BTreeMap::new(),
+    };
+    let mut rules = parser.file();
+    let broken = parser.broken;
+    // the core rules are spelled like the grammar uses them
+    let mut core = AbnfParser {
+        s: Scanner::builtin(CORE_RULES),
+        alloc,
+        errors: Errors::default(),
+        broken: vec![],
+        names: parser.names,
+    }
+    .file();
+    loop {
+        let defined: BTreeSet<&str> = rules.iter().map(|rule| rule.name).collect();
+        let mut missing = BTreeSet::new();
+        for prod in rules.iter().flat_map(|rule| rule.prod.iter()) {
+            visit(prod, &mut |prod| match prod {
+                // the grammar's own rule may be left out because of a syntax error
+                Prod::NonTerminal(name, _) if !defined.contains(name) && !broken.contains(name) => {
+                    missing.insert(*name);
+                }
+                _ => {}
+            });
+        }
+        let (used, rest): (Vec<_>, Vec<_>) = core
+            .into_iter()
+            .partition(|rule| missing.contains(rule.name));
+        if used.is_empty() {
+            break;
+        }
+        rules.extend(used);
+        core = rest;
+    }
+    (make_file(alloc, rules, broken), parser.errors.sorted())
+}
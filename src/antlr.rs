@@ -0,0 +1,332 @@
+// emitter of ANTLR4 combined grammars, keeping the ebnf operators
+use crate::emit::*;
+use crate::*;
+use std::collections::BTreeMap;
+
+// keywords of ANTLR, and of java, since rules become methods of the generated parser
+const KEYWORDS: &[&str] = &[
+    "import",
+    "fragment",
+    "lexer",
+    "parser",
+    "grammar",
+    "returns",
+    "locals",
+    "throws",
+    "catch",
+    "finally",
+    "mode",
+    "options",
+    "tokens",
+    "channels",
+    "abstract",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "final",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "transient",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+struct Antlr<'a> {
+    names: Names<'a>,
+    // lexer rules generated for character classes
+    tokens: Vec<(String, String)>,
+    // parser rules for classes matching literals, with their token and the literals
+    class_rules: Vec<(String, String, Vec<&'a str>)>,
+    values: BTreeMap<String, &'a str>,
+}
+
+// character inside a literal or a set, escaped
+fn escape(ch: char, special: &str) -> String {
+    match ch {
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\\' => "\\\\".to_string(),
+        _ if special.contains(ch) => format!("\\{}", ch),
+        ' ' => ch.to_string(),
+        _ if ch.is_ascii_graphic() => ch.to_string(),
+        _ if (ch as u32) > 0xFFFF => format!("\\u{{{:X}}}", ch as u32),
+        _ => format!("\\u{:04X}", ch as u32),
+    }
+}
+
+fn literal(value: &str) -> String {
+    let body: String = value.chars().map(|ch| escape(ch, "'")).collect();
+    format!("'{}'", body)
+}
+
+fn char_set(class: &CharClass) -> String {
+    let mut res = String::from(if class.negated { "~[" } else { "[" });
+    for (lo, hi) in class.ranges.iter() {
+        res.push_str(&escape(*lo, "]-"));
+        if lo != hi {
+            res.push('-');
+            res.push_str(&escape(*hi, "]-"));
+        }
+    }
+    res.push(']');
+    res
+}
+
+impl<'a> Antlr<'a> {
+    // parser rules start with a lower case letter
+    fn rule_name(&mut self, name: &'a str) -> String {
+        self.names.get(name, || {
+            let mut id = identifier(name);
+            // ALPHA reads better as alpha than aLPHA
+            if !id.contains(|ch: char| ch.is_ascii_lowercase()) {
+                id = id.to_ascii_lowercase();
+            }
+            let mut chars = id.chars();
+            match chars.next() {
+                Some(ch) if ch.is_ascii_alphabetic() => {
+                    ch.to_ascii_lowercase().to_string() + chars.as_str()
+                }
+                _ => format!("r{}", id),
+            }
+        })
+    }
+
+    // classes written alike share a lexer rule
+    // a class matching some literal is a parser rule, as the literal's own token comes first
+    fn class_token(&mut self, class: &'a CharClass<'a>) -> String {
+        let set = char_set(class);
+        let name = match self.tokens.iter().find(|(_, s)| *s == set) {
+            Some((name, _)) => name.clone(),
+            None => {
+                let index = self.tokens.len();
+                let name = self.names.get(class.raw, || format!("CLASS{}", index));
+                self.tokens.push((name.clone(), set));
+                let literals = class_literals(&self.values, class);
+                if !literals.is_empty() {
+                    let rule = self.names.fresh(name.to_ascii_lowercase());
+                    self.class_rules.push((rule, name.clone(), literals));
+                }
+                name
+            }
+        };
+        match self.class_rules.iter().find(|(_, token, _)| *token == name) {
+            Some((rule, ..)) => rule.clone(),
+            None => name,
+        }
+    }
+
+    // operand of a postfix operator
+    fn atom(&mut self, prod: &'a Prod<'a>) -> String {
+        let res = self.seq(prod);
+        match prod {
+            Prod::Terminal(..) | Prod::Class(..) | Prod::NonTerminal(..) | Prod::Alt(..) => res,
+            _ => format!("({})", res),
+        }
+    }
+
+    fn seq(&mut self, prod: &'a Prod<'a>) -> String {
+        match prod {
+            Prod::Concat(l, r, _) => match as_plus(prod) {
+                Some(p) => format!("{}+", self.atom(p)),
+                None => {
+                    let (l, r) = (self.seq(l), self.seq(r));
+                    match (l.is_empty(), r.is_empty()) {
+                        (true, _) => r,
+                        (_, true) => l,
+                        _ => format!("{} {}", l, r),
+                    }
+                }
+            },
+            // an empty literal matches nothing, which ANTLR doesn't allow
+            Prod::Terminal(lit, _) if lit.value.is_empty() => String::new(),
+            Prod::Terminal(lit, _) => literal(lit.value),
+            Prod::Class(class, _) => self.class_token(class),
+            Prod::NonTerminal(name, _) => self.rule_name(name),
+            Prod::Optional(p, _) => format!("{}?", self.atom(p)),
+            Prod::Star(p, _) => format!("{}*", self.atom(p)),
+            // there is no counted repetition, so copies are written out
+            Prod::Repeat(p, min, max, _) => {
+                let p = self.atom(p);
+                let mut res = vec![p.clone(); *min];
+                match max {
+                    None if *min > 0 => res[*min - 1].push('+'),
+                    None => res.push(format!("{}*", p)),
+                    Some(max) if max > min => {
+                        let mut tail = format!("{}?", p);
+                        for _ in min + 1..*max {
+                            tail = format!("({} {})?", p, tail);
+                        }
+                        res.push(tail);
+                    }
+                    Some(_) => {}
+                }
+                res.join(" ")
+            }
+            Prod::Alt(alts, _) => {
+                let alts: Vec<String> = alts.iter().map(|alt| self.seq(alt)).collect();
+                format!("({})", alts.join(" | "))
+            }
+            Prod::Eps(_) => String::new(),
+        }
+    }
+}
+
+// grammar is the name of the grammar, which ANTLR wants to match the file name
+pub fn emit_antlr(file: &File, grammar: &str) -> String {
+    use std::fmt::Write;
+    let mut antlr = Antlr {
+        names: Names::new(KEYWORDS),
+        tokens: vec![],
+        class_rules: vec![],
+        values: literal_values(file),
+    };
+    // reserved for the whitespace rule
+    antlr.names.get("", || "WS".to_string());
+    let mut res = String::new();
+    let _ = writeln!(res, "grammar {};", identifier(grammar));
+    if !file.precs.is_empty() {
+        res.push_str("\n// precedence from lowest to highest\n");
+        res.push_str("// ANTLR takes it from the order of alternatives instead\n");
+        for prec in file.precs.iter() {
+            let _ = writeln!(res, "// {:?} {}", prec.assoc, prec.terms.join(" "));
+        }
+    }
+    for (name, alts) in grouped_rules(file) {
+        let _ = writeln!(res, "\n{}", antlr.rule_name(name));
+        for (i, alt) in alts.into_iter().enumerate() {
+            let sep = if i == 0 { ':' } else { '|' };
+            let line = format!("    {} {}", sep, antlr.seq(alt));
+            let _ = writeln!(res, "{}", line.trim_end());
+        }
+        res.push_str("    ;\n");
+    }
+    for (rule, token, literals) in antlr.class_rules.iter() {
+        let _ = writeln!(res, "\n{}\n    : {}", rule, token);
+        for value in literals.iter() {
+            let _ = writeln!(res, "    | {}", literal(value));
+        }
+        res.push_str("    ;\n");
+    }
+    res.push('\n');
+    for (name, set) in antlr.tokens.iter() {
+        let _ = writeln!(res, "{} : {} ;", name, set);
+    }
+    if !matches_whitespace(file) {
+        res.push_str("WS : [ \\t\\r\\n]+ -> skip ;\n");
+    }
+    res
+}
+
+// whether some literal or class has whitespace, which then can't be skipped
+fn matches_whitespace(file: &File) -> bool {
+    let mut res = false;
+    for prod in file.rules.iter().flat_map(|rule| rule.prod.iter()) {
+        visit(prod, &mut |prod| match prod {
+            Prod::Terminal(lit, _) => res |= lit.value.contains(|ch| " \t\r\n".contains(ch)),
+            Prod::Class(class, _) => res |= " \t\r\n".chars().any(|ch| class.contains(ch)),
+            _ => {}
+        });
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(code: &str) -> String {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        emit_antlr(file, "expr")
+    }
+
+    #[test]
+    fn antlr_keeps_operators() {
+        let code = "%left '+';\nS ::= E;\nE ::= E '+' E | Int | '(' E ')';\n\
+                    Int ::= [0-9]+ | '0x' [0-9]{2,3};\nclass ::= 'x'?;\n";
+        let expected = "grammar expr;
+
+// precedence from lowest to highest
+// ANTLR takes it from the order of alternatives instead
+// Left '+'
+
+s
+    : e
+    ;
+
+e
+    : e '+' e
+    | int_
+    | '(' e ')'
+    ;
+
+int_
+    : CLASS0+
+    | '0x' CLASS0 CLASS0 CLASS0?
+    ;
+
+class_
+    : 'x'?
+    ;
+
+CLASS0 : [0-9] ;
+WS : [ \\t\\r\\n]+ -> skip ;
+";
+        assert_eq!(emitted(code), expected);
+    }
+
+    #[test]
+    fn antlr_classes_accept_their_literals() {
+        // whitespace in a literal is not skipped
+        let expected = "grammar expr;
+
+s
+    : class0 'if' 'a' ' '
+    ;
+
+class0
+    : CLASS0
+    | 'a'
+    ;
+
+CLASS0 : [a-z] ;
+";
+        assert_eq!(emitted("S ::= [a-z] 'if' 'a' ' ';"), expected);
+    }
+}
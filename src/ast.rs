@@ -1,5 +1,7 @@
+use crate::loc::{Loc, Span};
 use std::cell::RefCell;
 use std::collections::BTreeMap;
+use std::fmt;
 use typed_arena::Arena;
 
 #[derive(Default)]
@@ -7,28 +9,140 @@ pub struct ASTAlloc<'a> {
     pub file: Arena<File<'a>>,
     pub rule: Arena<RuleDef<'a>>,
     pub prod: Arena<Prod<'a>>,
+    pub string: Arena<String>,
 }
 
 #[derive(Debug)]
 pub struct File<'a> {
     pub rules: Vec<RuleDef<'a>>,
+    // from lowest to highest precedence
+    pub precs: Vec<PrecDef<'a>>,
+    // names of rules left out because of syntax errors
+    pub broken: Vec<&'a str>,
     pub mapping: RefCell<BTreeMap<&'a str, &'a RuleDef<'a>>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    NonAssoc,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrecDef<'a> {
+    pub assoc: Assoc,
+    // names of the terminals, see terminal_name
+    pub terms: Vec<&'a str>,
+    pub loc: Loc,
+}
+
 #[derive(Debug, Clone)]
 pub struct RuleDef<'a> {
     pub name: &'a str,
+    pub span: Span,
     pub prod: Vec<&'a Prod<'a>>,
 }
 
+// raw is the source text with quotes and escapes, value has them resolved
+#[derive(Debug, Clone, Copy)]
+pub struct Literal<'a> {
+    pub raw: &'a str,
+    pub value: &'a str,
+}
+
+// name of the terminal with value, the same however it is quoted and escaped in the source
+pub fn terminal_name(value: &str) -> String {
+    let mut res = String::from("'");
+    for ch in value.chars() {
+        match ch {
+            '\\' => res.push_str("\\\\"),
+            '\'' => res.push_str("\\'"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            _ if ch.is_control() => res.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            _ => res.push(ch),
+        }
+    }
+    res.push('\'');
+    res
+}
+
+// value of a quoted terminal like those named by terminal_name
+pub fn terminal_value(quoted: &str) -> String {
+    let mut chars = quoted[1..quoted.len() - 1].chars();
+    let mut res = String::new();
+    while let Some((ch, _)) = crate::next_char(&mut chars) {
+        res.push(ch);
+    }
+    res
+}
+
+// set of characters like [a-z_] or '0'..'9', raw is the source text and names the terminal
+#[derive(Clone, PartialEq, Eq, Ord, PartialOrd)]
+pub struct CharClass<'a> {
+    pub raw: &'a str,
+    pub negated: bool,
+    // inclusive ranges
+    pub ranges: Vec<(char, char)>,
+}
+
+impl<'a> CharClass<'a> {
+    pub fn contains(&self, ch: char) -> bool {
+        self.ranges.iter().any(|(lo, hi)| *lo <= ch && ch <= *hi) != self.negated
+    }
+
+    // some character matched by both classes
+    pub fn overlap(&self, other: &CharClass) -> Option<char> {
+        // the intersection starts at 0, at the start of a range or right after one
+        let mut candidates = vec!['\0', '\u{E000}'];
+        for (lo, hi) in self.ranges.iter().chain(other.ranges.iter()) {
+            candidates.push(*lo);
+            candidates.extend(std::char::from_u32(*hi as u32 + 1));
+        }
+        candidates
+            .into_iter()
+            .find(|ch| self.contains(*ch) && other.contains(*ch))
+    }
+}
+
+// printed as its source text like literals
+impl<'a> fmt::Debug for CharClass<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.raw)
+    }
+}
+
 #[derive(Debug)]
 pub enum Prod<'a> {
-    Concat(&'a Prod<'a>, &'a Prod<'a>),
-    Terminal(&'a str),
-    NonTerminal(&'a str),
-    Optional(&'a Prod<'a>),
-    Star(&'a Prod<'a>),
-    Eps,
+    Concat(&'a Prod<'a>, &'a Prod<'a>, Span),
+    Terminal(Literal<'a>, Span),
+    Class(CharClass<'a>, Span),
+    NonTerminal(&'a str, Span),
+    Optional(&'a Prod<'a>, Span),
+    Star(&'a Prod<'a>, Span),
+    // p{min,max}, unbounded without max
+    Repeat(&'a Prod<'a>, usize, Option<usize>, Span),
+    // alternatives inside parentheses, in source order
+    Alt(Vec<&'a Prod<'a>>, Span),
+    Eps(Span),
+}
+
+impl<'a> Prod<'a> {
+    pub fn span(&self) -> Span {
+        match self {
+            Prod::Concat(_, _, span)
+            | Prod::Terminal(_, span)
+            | Prod::Class(_, span)
+            | Prod::NonTerminal(_, span)
+            | Prod::Optional(_, span)
+            | Prod::Star(_, span)
+            | Prod::Repeat(_, _, _, span)
+            | Prod::Alt(_, span)
+            | Prod::Eps(span) => *span,
+        }
+    }
 }
 
 pub type FlatProds<'a> = Vec<&'a FlatProd<'a>>;
@@ -36,12 +150,15 @@ pub type FlatProds<'a> = Vec<&'a FlatProd<'a>>;
 #[derive(Debug, PartialEq, Eq)]
 pub struct FlatRuleDef<'a> {
     pub name: &'a str,
+    // the ebnf fragment this rule comes from
+    pub span: Span,
     pub prod: FlatProds<'a>,
 }
 
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Clone)]
 pub enum FlatProd<'a> {
     Terminal(&'a str),
+    Class(&'a CharClass<'a>),
     NonTerminal(&'a str),
     Eps,
 }
@@ -50,15 +167,27 @@ impl<'a> FlatProd<'a> {
     pub fn name(&self) -> &'a str {
         match self {
             FlatProd::Terminal(t) => t,
+            FlatProd::Class(c) => c.raw,
             FlatProd::NonTerminal(t) => t,
             FlatProd::Eps => "_",
         }
     }
 
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, FlatProd::Terminal(_) | FlatProd::Class(_))
+    }
+
     pub fn is_eps(&self) -> bool {
-        match self {
-            FlatProd::Eps => true,
-            _ => false,
+        matches!(self, FlatProd::Eps)
+    }
+}
+
+impl<'a> fmt::Display for FlatRuleDef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ::=", self.name)?;
+        for prod in self.prod.iter() {
+            write!(f, " {}", prod.name())?;
         }
+        Ok(())
     }
 }
@@ -0,0 +1,71 @@
+use clap::{App, Arg};
+use ebnf_tools::*;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let matches = App::new("convert")
+        .arg(
+            Arg::with_name("file")
+                .value_name("file")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(Format::NAMES)
+                .help("Dialect of the grammar file, guessed from its extension by default"),
+        )
+        .arg(
+            Arg::with_name("to")
+                .long("to")
+                .takes_value(true)
+                .possible_values(&["antlr", "bison", "pest", "lalrpop", "lalr1"])
+                .default_value("antlr")
+                .help("Grammar format to write"),
+        )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .help("Name of the generated grammar, the file name by default"),
+        )
+        .get_matches();
+    let opts = matches.value_of("file").unwrap();
+    let code = fs::read_to_string(opts).unwrap();
+    let ast_alloc = ASTAlloc::default();
+    let format = matches
+        .value_of("format")
+        .and_then(Format::from_name)
+        .unwrap_or_else(|| Format::from_path(opts));
+    let (ebnf, failed) = format.load(opts, &code, &ast_alloc);
+    // the rules that parsed are not the grammar, so nothing is converted
+    if failed {
+        process::exit(1);
+    }
+    let stem = Path::new(opts)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("grammar");
+    let name = matches.value_of("name").unwrap_or(stem);
+    match matches.value_of("to").unwrap() {
+        "antlr" => print!("{}", emit_antlr(ebnf, name)),
+        "bison" => {
+            let flatten_alloc = FlattenAlloc::default();
+            let rules = flatten(ebnf, &flatten_alloc);
+            let graph = lr_graph(&rules);
+            print!("{}", emit_bison(ebnf, &rules, &graph));
+        }
+        "pest" => print!("{}", emit_pest(ebnf)),
+        "lalrpop" => print!("{}", emit_lalrpop(ebnf)),
+        "lalr1" => {
+            let flatten_alloc = FlattenAlloc::default();
+            let rules = flatten(ebnf, &flatten_alloc);
+            print!("{}", emit_lalr1(ebnf, &rules));
+        }
+        _ => unreachable!(),
+    }
+}
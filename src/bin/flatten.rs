@@ -1,6 +1,7 @@
 use clap::{App, Arg};
 use ebnf_tools::*;
 use std::fs;
+use std::process;
 
 fn main() {
     let matches = App::new("generate")
@@ -10,25 +11,52 @@ fn main() {
                 .takes_value(true)
                 .required(true),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(Format::NAMES)
+                .help("Dialect of the grammar file, guessed from its extension by default"),
+        )
+        .arg(
+            Arg::with_name("eliminate-left-recursion")
+                .long("eliminate-left-recursion")
+                .help("Remove left recursion from flattened rules"),
+        )
+        .arg(
+            Arg::with_name("left-factor")
+                .long("left-factor")
+                .help("Factor common prefixes of flattened rules"),
+        )
         .get_matches();
     let opts = matches.value_of("file").unwrap();
+    let eliminate = matches.is_present("eliminate-left-recursion");
+    let factor = matches.is_present("left-factor");
     let code = fs::read_to_string(opts).unwrap();
     let ast_alloc = ASTAlloc::default();
     let flatten_alloc = FlattenAlloc::default();
-    let ebnf = work(&code, &ast_alloc);
-    if let Ok(ebnf) = ebnf {
-        let res = flatten(&ebnf, &flatten_alloc);
-        for rule in res {
-            print!("{} ::=", rule.name);
-            for prod in rule.prod {
-                match prod {
-                    FlatProd::Terminal(name) | FlatProd::NonTerminal(name) => print!(" {}", name),
-                    FlatProd::Eps => print!(" _"),
-                }
-            }
-            println!();
-        }
-    } else {
-        println!("{:?}", ebnf.unwrap_err());
+    let format = matches
+        .value_of("format")
+        .and_then(Format::from_name)
+        .unwrap_or_else(|| Format::from_path(opts));
+    let (ebnf, failed) = format.load(opts, &code, &ast_alloc);
+    // analysis runs on the rules that parsed, the exit code still tells about the errors
+    if ebnf.rules.is_empty() {
+        process::exit(1);
+    }
+    let mut res = flatten(ebnf, &flatten_alloc);
+    if eliminate {
+        let (rules, errors) = eliminate_left_recursion(&res, &flatten_alloc);
+        errors.report(opts, &code);
+        res = rules;
+    }
+    if factor {
+        res = left_factor(&res, &flatten_alloc);
+    }
+    for rule in res {
+        println!("{}", rule);
+    }
+    if failed {
+        process::exit(1);
     }
 }
@@ -1,7 +1,7 @@
 use clap::{App, Arg};
-use ebnf_tools;
-use ebnf_tools::Generate;
+use ebnf_tools::*;
 use std::fs;
+use std::process;
 
 fn main() {
     let matches = App::new("generate")
@@ -11,14 +11,25 @@ fn main() {
                 .takes_value(true)
                 .required(true),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(Format::NAMES)
+                .help("Dialect of the grammar file, guessed from its extension by default"),
+        )
         .get_matches();
     let opts = matches.value_of("file").unwrap();
     let code = fs::read_to_string(opts).unwrap();
-    let alloc = ebnf_gen::ASTAlloc::default();
-    let ebnf = ebnf_gen::work(&code, &alloc);
-    if let Ok(ebnf) = ebnf {
-        println!("{}", ebnf.generate(&ebnf, 30));
-    } else {
-        println!("{:?}", ebnf.unwrap_err());
+    let alloc = ASTAlloc::default();
+    let format = matches
+        .value_of("format")
+        .and_then(Format::from_name)
+        .unwrap_or_else(|| Format::from_path(opts));
+    let (ebnf, failed) = format.load(opts, &code, &alloc);
+    // generating from non productive or missing rules would never end
+    if failed || ebnf.rules.is_empty() {
+        process::exit(1);
     }
+    println!("{}", ebnf.generate(ebnf, 30));
 }
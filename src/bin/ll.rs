@@ -1,6 +1,7 @@
 use clap::{App, Arg};
 use ebnf_tools::*;
 use std::fs;
+use std::process;
 
 fn main() {
     let matches = App::new("generate")
@@ -10,37 +11,124 @@ fn main() {
                 .takes_value(true)
                 .required(true),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(Format::NAMES)
+                .help("Dialect of the grammar file, guessed from its extension by default"),
+        )
         .arg(Arg::with_name("first").short("f").help("Print FIRST set"))
         .arg(Arg::with_name("follow").short("F").help("Print FOLLOW set"))
+        .arg(
+            Arg::with_name("ll1")
+                .short("T")
+                .long("table")
+                .help("Print ll(1) table"),
+        )
+        .arg(
+            Arg::with_name("left-factor")
+                .long("left-factor")
+                .help("Factor common prefixes of rules before analysis"),
+        )
+        .arg(
+            Arg::with_name("input")
+                .short("i")
+                .long("input")
+                .takes_value(true)
+                .help("Parse input sentence with ll(1) table"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .short("t")
+                .long("trace")
+                .takes_value(true)
+                .possible_values(&["plain", "markdown", "latex"])
+                .requires("input")
+                .help("Print each step of parsing input"),
+        )
         .get_matches();
     let opts = matches.value_of("file").unwrap();
     let first = matches.is_present("first");
     let follow = matches.is_present("follow");
+    let ll1 = matches.is_present("ll1");
+    let factor = matches.is_present("left-factor");
+    let input = matches.value_of("input").map(split_terminals);
+    let trace = matches.value_of("trace").and_then(TraceFormat::from_name);
     let code = fs::read_to_string(opts).unwrap();
     let ast_alloc = ASTAlloc::default();
     let flatten_alloc = FlattenAlloc::default();
-    let ebnf = work(&code, &ast_alloc);
-    if let Ok(ebnf) = ebnf {
-        let res = flatten(&ebnf, &flatten_alloc);
-        let first_s = first_set(&res);
-        let follow_s = follow_set(&res, &first_s);
-        if first {
-            let mut first: Vec<_> = first_s.iter().collect();
-            first.sort();
-            println!("FIRST:");
-            for item in first {
-                println!("{}:  {:?}", item.0, item.1);
-            }
+    let format = matches
+        .value_of("format")
+        .and_then(Format::from_name)
+        .unwrap_or_else(|| Format::from_path(opts));
+    let (ebnf, failed) = format.load(opts, &code, &ast_alloc);
+    // analysis runs on the rules that parsed, the exit code still tells about the errors
+    if ebnf.rules.is_empty() {
+        process::exit(1);
+    }
+    let mut res = flatten(ebnf, &flatten_alloc);
+    if factor {
+        res = left_factor(&res, &flatten_alloc);
+    }
+    let first_s = first_set(&res);
+    let follow_s = follow_set(&res, &first_s);
+    if first {
+        let mut first: Vec<_> = first_s.iter().collect();
+        first.sort();
+        println!("FIRST:");
+        for item in first {
+            println!("{}:  {:?}", item.0, item.1);
+        }
+        for overlap in class_overlaps("FIRST", &first_s) {
+            print!("{}", overlap);
+        }
+    }
+    if follow {
+        let mut follow: Vec<_> = follow_s.iter().collect();
+        follow.sort();
+        println!("FOLLOW:");
+        for item in follow {
+            println!("{}:  {:?}", item.0, item.1);
         }
-        if follow {
-            let mut follow: Vec<_> = follow_s.iter().collect();
-            follow.sort();
-            println!("FOLLOW:");
-            for item in follow {
-                println!("{}:  {:?}", item.0, item.1);
+        for overlap in class_overlaps("FOLLOW", &follow_s) {
+            print!("{}", overlap);
+        }
+    }
+    let table = ll1_table(&res, &first_s, &follow_s);
+    let mut errors = 0;
+    if ll1 {
+        println!("LL(1) Table:");
+        print!("{}", table);
+        let conflicts = table.conflicts();
+        for conflict in conflicts.iter() {
+            print!("{}", conflict);
+        }
+        errors += conflicts
+            .iter()
+            .filter(|conflict| !conflict.is_warning())
+            .count();
+    }
+    if let Some(input) = input.as_ref() {
+        let input: Vec<&str> = input.iter().map(|s| s.as_str()).collect();
+        println!("LL(1) Parse:");
+        let res = match trace {
+            Some(format) => {
+                let (steps, res) = ll_trace(&table, &input);
+                print!("{}", format_ll_trace(&table, &steps, format));
+                res
+            }
+            None => ll_parse(&table, &input),
+        };
+        match res {
+            Ok(tree) => print!("{}", tree),
+            Err(err) => {
+                println!("{}", err);
+                errors += 1;
             }
         }
-    } else {
-        println!("{:?}", ebnf.unwrap_err());
+    }
+    if failed || errors > 0 {
+        process::exit(1);
     }
 }
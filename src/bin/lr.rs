@@ -1,6 +1,49 @@
 use clap::{App, Arg};
 use ebnf_tools::*;
 use std::fs;
+use std::process;
+
+// print table, its conflicts and the ones resolved by precedence, then parse input if any
+// returns number of unresolved conflicts, overlaps aside, and parse errors
+fn print_table<'a>(
+    name: &str,
+    table: LrTable<'a>,
+    input: Option<&[&'a str]>,
+    trace: Option<TraceFormat>,
+) -> usize {
+    println!("{} Table:", name);
+    print!("{}", table);
+    for resolution in table.resolutions().iter() {
+        print!("{}", resolution);
+    }
+    let conflicts = table.conflicts();
+    for conflict in conflicts.iter() {
+        print!("{}", conflict);
+    }
+    let mut errors = conflicts
+        .iter()
+        .filter(|conflict| !conflict.is_warning())
+        .count();
+    if let Some(input) = input {
+        println!("{} Parse:", name);
+        let res = match trace {
+            Some(format) => {
+                let (steps, res) = lr_trace(&table, input);
+                print!("{}", format_lr_trace(&steps, format));
+                res
+            }
+            None => lr_parse(&table, input),
+        };
+        match res {
+            Ok(tree) => print!("{}", tree),
+            Err(err) => {
+                println!("{}", err);
+                errors += 1;
+            }
+        }
+    }
+    errors
+}
 
 fn main() {
     let matches = App::new("generate")
@@ -10,6 +53,13 @@ fn main() {
                 .takes_value(true)
                 .required(true),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(Format::NAMES)
+                .help("Dialect of the grammar file, guessed from its extension by default"),
+        )
         .arg(Arg::with_name("dot").short("d").help("Print graphviz"))
         .arg(
             Arg::with_name("plain")
@@ -18,43 +68,90 @@ fn main() {
         )
         .arg(Arg::with_name("lr0").short("l").help("Print lr(0) table"))
         .arg(Arg::with_name("slr1").short("s").help("Print slr(1) table"))
+        .arg(
+            Arg::with_name("lalr1")
+                .short("a")
+                .help("Print lalr(1) table"),
+        )
+        .arg(
+            Arg::with_name("lr1")
+                .short("c")
+                .help("Print canonical lr(1) table"),
+        )
         .arg(
             Arg::with_name("flatten")
                 .short("f")
                 .help("Print flattened rules"),
         )
+        .arg(
+            Arg::with_name("input")
+                .short("i")
+                .long("input")
+                .takes_value(true)
+                .help("Parse input sentence with each table, lalr(1) by default"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .short("t")
+                .long("trace")
+                .takes_value(true)
+                .possible_values(&["plain", "markdown", "latex"])
+                .requires("input")
+                .help("Print each step of parsing input"),
+        )
         .get_matches();
     let opts = matches.value_of("file").unwrap();
     let dot = matches.is_present("dot");
     let plain = matches.is_present("plain");
     let lr0 = matches.is_present("lr0");
     let slr1 = matches.is_present("slr1");
+    let lr1 = matches.is_present("lr1");
+    let input = matches.value_of("input").map(split_terminals);
+    let trace = matches.value_of("trace").and_then(TraceFormat::from_name);
+    let lalr1 = matches.is_present("lalr1") || (input.is_some() && !(lr0 || slr1 || lr1));
     let flattened = matches.is_present("flatten");
     let code = fs::read_to_string(opts).unwrap();
     let ast_alloc = ASTAlloc::default();
     let flatten_alloc = FlattenAlloc::default();
-    let ebnf = work(&code, &ast_alloc);
-    if let Ok(ebnf) = ebnf {
-        let res = flatten(&ebnf, &flatten_alloc);
-        let lr = lr_graph(&res);
-        if flattened {
-            println!("{:?}", res);
-        }
-        if dot {
-            println!("{}", lr.print_dot().unwrap());
-        }
-        if plain {
-            println!("{}", lr);
-        }
-        if lr0 {
-            println!("LR(0) Table:");
-            print!("{}", lr0_table(&lr));
-        }
-        if slr1 {
-            println!("SLR(1) Table:");
-            print!("{}", slr1_table(&lr));
-        }
-    } else {
-        println!("{:?}", ebnf.unwrap_err());
+    let format = matches
+        .value_of("format")
+        .and_then(Format::from_name)
+        .unwrap_or_else(|| Format::from_path(opts));
+    let (ebnf, failed) = format.load(opts, &code, &ast_alloc);
+    // analysis runs on the rules that parsed, the exit code still tells about the errors
+    if ebnf.rules.is_empty() {
+        process::exit(1);
+    }
+    let res = flatten(ebnf, &flatten_alloc);
+    let lr = lr_graph(&res);
+    if flattened {
+        println!("{:?}", res);
+    }
+    if dot {
+        println!("{}", lr.print_dot().unwrap());
+    }
+    if plain {
+        println!("{}", lr);
+    }
+    let input: Option<Vec<&str>> = input
+        .as_ref()
+        .map(|input| input.iter().map(|s| s.as_str()).collect());
+    let input = input.as_ref().map(|input| &input[..]);
+    let mut conflicts = 0;
+    if lr0 {
+        conflicts += print_table("LR(0)", lr0_table(&lr, &ebnf.precs), input, trace);
+    }
+    if slr1 {
+        conflicts += print_table("SLR(1)", slr1_table(&lr, &ebnf.precs), input, trace);
+    }
+    if lalr1 {
+        conflicts += print_table("LALR(1)", lalr1_table(&lr, &ebnf.precs), input, trace);
+    }
+    if lr1 {
+        let lr1_lr = lr1_graph(&res);
+        conflicts += print_table("LR(1)", lr1_table(&lr1_lr, &ebnf.precs), input, trace);
+    }
+    if failed || conflicts > 0 {
+        process::exit(1);
     }
 }
@@ -0,0 +1,167 @@
+// emitter of bison grammars from flattened rules, to compare tables with bison's
+use crate::emit::*;
+use crate::*;
+
+const KEYWORDS: &[&str] = &["error", "YYEOF", "YYUNDEF", "YYerror"];
+
+// string in c syntax, for the alias of a token
+fn c_string(value: &str) -> String {
+    let mut res = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            _ if ch.is_control() => res.push_str(&format!("\\{:03o}", ch as u32)),
+            _ => res.push(ch),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn token_name(value: &str) -> String {
+    identifier(&token_words(value).join("_").to_ascii_uppercase())
+}
+
+// tokens are named after the value of their literal, classes are numbered
+pub fn emit_bison(file: &File, rules: &[FlatRuleDef], graph: &LrGraph) -> String {
+    use std::fmt::Write;
+    let values = literal_values(file);
+    let mut names = Names::new(KEYWORDS);
+    for rule in rules.iter() {
+        names.get(rule.name, || identifier(rule.name));
+    }
+    let mut res = String::new();
+    // each literal string may alias only one token
+    let mut aliases = vec![];
+    let mut classes = 0;
+    // terminals of unreachable rules are not in the graph
+    let mut terminals: Vec<&str> = graph.terminals().iter().cloned().collect();
+    for prod in rules.iter().flat_map(|rule| rule.prod.iter()) {
+        if prod.is_terminal() && !terminals.contains(&prod.name()) {
+            terminals.push(prod.name());
+        }
+    }
+    for terminal in terminals.into_iter().filter(|t| *t != "#") {
+        let value = values.get(terminal).cloned();
+        let name = names.get(terminal, || match value {
+            Some(value) => token_name(value),
+            None => {
+                classes += 1;
+                format!("CLASS{}", classes - 1)
+            }
+        });
+        let alias = c_string(value.unwrap_or(terminal));
+        if aliases.contains(&alias) {
+            let _ = writeln!(res, "%token {}", name);
+        } else {
+            let _ = writeln!(res, "%token {} {}", name, alias);
+            aliases.push(alias);
+        }
+    }
+    for prec in file.precs.iter() {
+        let assoc = match prec.assoc {
+            Assoc::Left => "%left",
+            Assoc::Right => "%right",
+            Assoc::NonAssoc => "%nonassoc",
+        };
+        let terms: Vec<String> = prec
+            .terms
+            .iter()
+            // terminals declared but unused only have their quoted text
+            .map(|term| names.get(term, || token_name(&terminal_value(term))))
+            .collect();
+        let _ = writeln!(res, "{} {}", assoc, terms.join(" "));
+    }
+    if let Some(start) = rules.first() {
+        let _ = writeln!(res, "%start {}", names.get(start.name, || unreachable!()));
+    }
+    res.push_str("\n%%\n");
+
+    // alternatives of a non terminal are kept together, in their order
+    let mut order: Vec<&str> = vec![];
+    for rule in rules.iter() {
+        if !order.contains(&rule.name) {
+            order.push(rule.name);
+        }
+    }
+    for name in order {
+        let _ = writeln!(res, "\n{}", names.get(name, || unreachable!()));
+        for (i, rule) in rules.iter().filter(|rule| rule.name == name).enumerate() {
+            let symbols: Vec<String> = rule
+                .prod
+                .iter()
+                .filter(|prod| !prod.is_eps())
+                .map(|prod| names.get(prod.name(), || unreachable!()))
+                .collect();
+            let body = if symbols.is_empty() {
+                "%empty".to_string()
+            } else {
+                symbols.join(" ")
+            };
+            let _ = writeln!(res, "    {} {}", if i == 0 { ':' } else { '|' }, body);
+        }
+        res.push_str("    ;\n");
+    }
+    res.push_str("\n%%\n");
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(code: &str) -> String {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        emit_bison(file, &rules, &lr_graph(&rules))
+    }
+
+    #[test]
+    fn bison_names_tokens_by_value() {
+        // '-' is only declared, e is taken by a rule and error by bison
+        let code = "%left '+' '-';\n%right '^';\nS ::= E;\n\
+                    E ::= E '+' E | E '^' E | [0-9] | '\"' E? '\"' | error;\nerror ::= 'e';\n";
+        let expected = r#"%token DQUOTE "\""
+%token PLUS "+"
+%token CARET "^"
+%token E1 "e"
+%token CLASS0 "[0-9]"
+%left PLUS MINUS
+%right CARET
+%start S
+
+%%
+
+S
+    : E
+    ;
+
+E
+    : error_
+    | DQUOTE E_opt0 DQUOTE
+    | CLASS0
+    | E CARET E
+    | E PLUS E
+    ;
+
+E_opt0
+    : E
+    | %empty
+    ;
+
+error_
+    : E1
+    ;
+
+%%
+"#;
+        assert_eq!(emitted(code), expected);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub fn visit<'a>(prod: &Prod<'a>, f: &mut impl FnMut(&Prod<'a>)) {
+    f(prod);
+    match prod {
+        Prod::Concat(l, r, _) => {
+            visit(l, f);
+            visit(r, f);
+        }
+        Prod::Optional(p, _) | Prod::Star(p, _) | Prod::Repeat(p, ..) => visit(p, f),
+        Prod::Alt(alts, _) => alts.iter().for_each(|p| visit(p, f)),
+        Prod::Terminal(..) | Prod::Class(..) | Prod::NonTerminal(..) | Prod::Eps(_) => {}
+    }
+}
+
+fn is_productive(prod: &Prod, productive: &BTreeSet<&str>) -> bool {
+    match prod {
+        Prod::Concat(l, r, _) => is_productive(l, productive) && is_productive(r, productive),
+        Prod::NonTerminal(name, _) => productive.contains(name),
+        Prod::Alt(alts, _) => alts.iter().any(|p| is_productive(p, productive)),
+        Prod::Repeat(p, min, ..) => *min == 0 || is_productive(p, productive),
+        Prod::Terminal(..)
+        | Prod::Class(..)
+        | Prod::Optional(..)
+        | Prod::Star(..)
+        | Prod::Eps(_) => true,
+    }
+}
+
+// non terminals deriving at least one terminal string
+// rules left out because of syntax errors are taken as productive
+pub fn productive<'a>(file: &File<'a>) -> BTreeSet<&'a str> {
+    let mut res: BTreeSet<&str> = file.broken.iter().cloned().collect();
+    loop {
+        let mut changed = false;
+        for rule in file.rules.iter() {
+            if !res.contains(rule.name) && rule.prod.iter().any(|prod| is_productive(prod, &res)) {
+                res.insert(rule.name);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    res
+}
+
+// semantic checks after parsing: undefined non terminals, unreachable rules,
+// duplicate rule definitions, non productive rules and declared but unused terminals
+pub fn check(file: &File) -> Errors {
+    let mut errors = Errors::default();
+
+    // duplicate definitions, a rule head repeated for each alternative in a row is fine
+    let mut defined: BTreeMap<&str, Loc> = BTreeMap::new();
+    let mut prev = None;
+    for rule in file.rules.iter() {
+        match defined.get(rule.name) {
+            Some(_) if prev == Some(rule.name) => {}
+            Some(first) => errors.issue(
+                rule.span.start,
+                ErrorKind::DuplicateRule(rule.name.to_string(), *first),
+            ),
+            None => {
+                defined.insert(rule.name, rule.span.start);
+            }
+        }
+        prev = Some(rule.name);
+    }
+
+    // undefined non terminals and used terminals
+    let mut used_terminals = BTreeSet::new();
+    for rule in file.rules.iter() {
+        for prod in rule.prod.iter() {
+            visit(prod, &mut |prod| match prod {
+                Prod::NonTerminal(name, span)
+                    if !defined.contains_key(name) && !file.broken.contains(name) =>
+                {
+                    errors.issue(
+                        span.start,
+                        ErrorKind::UndefinedNonTerminal(name.to_string()),
+                    )
+                }
+                Prod::Class(class, span) if class.overlap(class).is_none() => {
+                    errors.issue(span.start, ErrorKind::EmptyClass(class.raw.to_string()))
+                }
+                Prod::Repeat(_, min, Some(max), span) if max < min => {
+                    errors.issue(span.start, ErrorKind::EmptyRepeat(*min, *max))
+                }
+                Prod::Terminal(lit, _) => {
+                    used_terminals.insert(terminal_name(lit.value));
+                }
+                _ => {}
+            });
+        }
+    }
+
+    // rules unreachable from the first rule
+    // unknown when the rules reached include one left out because of syntax errors
+    if let Some(start) = file.rules.first() {
+        let mut reachable = BTreeSet::new();
+        let mut pending = vec![start.name];
+        while let Some(name) = pending.pop() {
+            if !reachable.insert(name) {
+                continue;
+            }
+            for rule in file.rules.iter().filter(|rule| rule.name == name) {
+                for prod in rule.prod.iter() {
+                    visit(prod, &mut |prod| {
+                        if let Prod::NonTerminal(name, _) = prod {
+                            pending.push(name);
+                        }
+                    });
+                }
+            }
+        }
+        let known = !file.broken.iter().any(|name| reachable.contains(name));
+        let mut reported = BTreeSet::new();
+        for rule in file.rules.iter() {
+            if known && !reachable.contains(rule.name) && reported.insert(rule.name) {
+                errors.issue(
+                    rule.span.start,
+                    ErrorKind::UnreachableRule(rule.name.to_string()),
+                )
+            }
+        }
+    }
+
+    // rules never deriving a terminal string
+    let productive = productive(file);
+    let mut reported = BTreeSet::new();
+    for rule in file.rules.iter() {
+        if !productive.contains(rule.name) && reported.insert(rule.name) {
+            errors.issue(
+                rule.span.start,
+                ErrorKind::NonProductive(rule.name.to_string()),
+            )
+        }
+    }
+
+    // terminals only appearing in precedence declarations
+    for prec in file.precs.iter() {
+        for term in prec.terms.iter() {
+            if !used_terminals.contains(*term) {
+                errors.issue(prec.loc, ErrorKind::UnusedTerminal(term.to_string()))
+            }
+        }
+    }
+
+    errors.sorted()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // messages of the checks on code, which must parse
+    fn checked(code: &str) -> Vec<String> {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        check(file).0.iter().map(|e| format!("{:?}", e)).collect()
+    }
+
+    #[test]
+    fn check_reports_rules_and_terminals() {
+        let code = "%left '+';\nS ::= A;\nA ::= 'a';\nA ::= 'c';\nU ::= 'u';\nA ::= 'b';\n";
+        assert_eq!(
+            checked(code),
+            vec![
+                "*** Warning at (1,1): terminal '+' is declared but never used",
+                "*** Warning at (5,1): rule U is unreachable from start symbol",
+                "*** Warning at (6,1): rule A is defined again, first definition is at (3,1)",
+            ]
+        );
+        assert!(checked("S ::= A 'x' B;\nA ::= 'a';\n")
+            .contains(&"*** Error at (1,13): non terminal B is not defined".to_string()));
+    }
+
+    #[test]
+    fn productive_needs_a_base_case() {
+        let alloc = ASTAlloc::default();
+        let (file, _) = work("S ::= A | B;\nA ::= A 'x';\nB ::= 'b' B?;\n", &alloc);
+        let expected: BTreeSet<&str> = vec!["B", "S"].into_iter().collect();
+        assert_eq!(productive(file), expected);
+        assert_eq!(
+            checked("S ::= A;\nA ::= A 'x';\n"),
+            vec![
+                "*** Error at (1,1): rule S never derives a terminal string",
+                "*** Error at (2,1): rule A never derives a terminal string",
+            ]
+        );
+    }
+
+    #[test]
+    fn repetitions_need_bounds_in_order() {
+        assert_eq!(
+            checked("S ::= 'a'{3,2} 'b'{0};"),
+            vec!["*** Error at (1,7): repetition matches nothing, its minimum 3 is above its maximum 2"]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+// shared pieces of the emitters for other parser generators
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+// rules grouped by non terminal in order of first definition, with all their alternatives
+// in source order, the ebnf parser collects them in reverse
+pub fn grouped_rules<'a>(file: &File<'a>) -> Vec<(&'a str, Vec<&'a Prod<'a>>)> {
+    let mut res: Vec<(&'a str, Vec<&'a Prod<'a>>)> = vec![];
+    for rule in file.rules.iter() {
+        // other front-ends may put all alternatives in a single group
+        let prods = rule.prod.iter().flat_map(|prod| match prod {
+            Prod::Alt(alts, _) => alts.clone(),
+            _ => vec![*prod],
+        });
+        match res.iter_mut().find(|(name, _)| *name == rule.name) {
+            Some((_, alts)) => alts.extend(prods),
+            None => res.push((rule.name, prods.collect())),
+        }
+    }
+    for (_, alts) in res.iter_mut() {
+        alts.sort_by_key(|alt| alt.span().lo);
+    }
+    res
+}
+
+// p+ is parsed into p p*, sharing p
+pub fn as_plus<'a>(prod: &'a Prod<'a>) -> Option<&'a Prod<'a>> {
+    match prod {
+        Prod::Concat(l, Prod::Star(r, _), _) if std::ptr::eq(*l, *r) => Some(l),
+        _ => None,
+    }
+}
+
+// letters, digits and _ only, not starting with a digit
+pub fn identifier(name: &str) -> String {
+    let res: String = name
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect();
+    if res.starts_with(|ch: char| ch.is_ascii_digit()) || res.is_empty() {
+        format!("_{}", res)
+    } else {
+        res
+    }
+}
+
+pub const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield",
+];
+
+// identifier in CamelCase, like TopLevel for topLevel or hier-part
+pub fn camel_case(name: &str) -> String {
+    let mut res = String::new();
+    for word in identifier(name).split('_').filter(|word| !word.is_empty()) {
+        let mut chars = word.chars();
+        res.extend(chars.next().map(|ch| ch.to_ascii_uppercase()));
+        res.push_str(chars.as_str());
+    }
+    identifier(&res)
+}
+
+// a literal as a regular expression
+pub fn regex_literal(value: &str) -> String {
+    let mut res = String::new();
+    for ch in value.chars() {
+        if "\\.+*?()|[]{}^$".contains(ch) {
+            res.push('\\');
+        }
+        res.push_str(&regex_char(ch));
+    }
+    res
+}
+
+fn regex_char(ch: char) -> String {
+    match ch {
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        _ if ch.is_control() || ch.is_whitespace() && ch != ' ' => {
+            format!("\\x{{{:X}}}", ch as u32)
+        }
+        _ => ch.to_string(),
+    }
+}
+
+// a character class as a regular expression
+pub fn regex_class(class: &CharClass) -> String {
+    let escape = |ch: char| {
+        let prefix = if "\\[]^-&~".contains(ch) { "\\" } else { "" };
+        format!("{}{}", prefix, regex_char(ch))
+    };
+    let mut res = String::from(if class.negated { "[^" } else { "[" });
+    for (lo, hi) in class.ranges.iter() {
+        res.push_str(&escape(*lo));
+        if lo != hi {
+            res.push('-');
+            res.push_str(&escape(*hi));
+        }
+    }
+    res.push(']');
+    res
+}
+
+// identifiers of a target language, unique and not colliding with its keywords
+pub struct Names<'a> {
+    keywords: Vec<&'static str>,
+    map: BTreeMap<&'a str, String>,
+    used: BTreeSet<String>,
+}
+
+impl<'a> Names<'a> {
+    pub fn new(keywords: &[&'static str]) -> Names<'a> {
+        Names {
+            keywords: keywords.to_vec(),
+            map: BTreeMap::new(),
+            used: BTreeSet::new(),
+        }
+    }
+
+    // the identifier of name, derived from want the first time
+    pub fn get(&mut self, name: &'a str, want: impl FnOnce() -> String) -> String {
+        if let Some(res) = self.map.get(name) {
+            return res.clone();
+        }
+        let res = self.fresh(want());
+        self.map.insert(name, res.clone());
+        res
+    }
+
+    // an identifier like want not used yet, for symbols the grammar doesn't have
+    pub fn fresh(&mut self, mut want: String) -> String {
+        if self.keywords.contains(&want.as_str()) {
+            want.push('_');
+        }
+        let mut res = want.clone();
+        let mut index = 1;
+        while self.used.contains(&res) {
+            res = format!("{}{}", want, index);
+            index += 1;
+        }
+        self.used.insert(res.clone());
+        res
+    }
+}
+
+// values of the terminals by their name, see terminal_name
+pub fn literal_values<'a>(file: &File<'a>) -> BTreeMap<String, &'a str> {
+    let mut res = BTreeMap::new();
+    for prod in file.rules.iter().flat_map(|rule| rule.prod.iter()) {
+        visit(prod, &mut |prod| {
+            if let Prod::Terminal(lit, _) = prod {
+                res.insert(terminal_name(lit.value), lit.value);
+            }
+        });
+    }
+    res
+}
+
+// values of the literals of one character matched by class
+// lexers make them tokens of their own, so the class token no longer matches them
+pub fn class_literals<'a>(values: &BTreeMap<String, &'a str>, class: &CharClass) -> Vec<&'a str> {
+    values
+        .values()
+        .filter(|value| {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => class.contains(ch),
+                _ => false,
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+fn punct_name(ch: char) -> Option<&'static str> {
+    Some(match ch {
+        '+' => "plus",
+        '-' => "minus",
+        '*' => "star",
+        '/' => "slash",
+        '%' => "percent",
+        '=' => "eq",
+        '<' => "lt",
+        '>' => "gt",
+        '!' => "bang",
+        '&' => "amp",
+        '|' => "bar",
+        '^' => "caret",
+        '~' => "tilde",
+        '(' => "lparen",
+        ')' => "rparen",
+        '[' => "lbrack",
+        ']' => "rbrack",
+        '{' => "lbrace",
+        '}' => "rbrace",
+        ',' => "comma",
+        ';' => "semi",
+        '.' => "dot",
+        ':' => "colon",
+        '?' => "question",
+        '#' => "hash",
+        '@' => "at",
+        '$' => "dollar",
+        '\'' => "quote",
+        '"' => "dquote",
+        '`' => "backquote",
+        '\\' => "backslash",
+        '_' => "underscore",
+        ' ' => "space",
+        '\t' => "tab",
+        '\n' => "newline",
+        '\r' => "cr",
+        _ => return None,
+    })
+}
+
+// lower case words naming a terminal by its value, like [gt, eq] for >=
+// words are kept together, punctuation is spelled out
+pub fn token_words(value: &str) -> Vec<String> {
+    let mut res = vec![];
+    let mut word = String::new();
+    let blank = value.chars().all(char::is_whitespace);
+    for ch in value.chars() {
+        if ch.is_ascii_alphanumeric() {
+            word.push(ch.to_ascii_lowercase());
+            continue;
+        }
+        if !word.is_empty() {
+            res.push(std::mem::take(&mut word));
+        }
+        match punct_name(ch) {
+            // spaces only separate words, unless there is nothing else
+            _ if ch.is_whitespace() && !blank => {}
+            Some(name) => res.push(name.to_string()),
+            None => res.push(format!("u{:x}", ch as u32)),
+        }
+    }
+    if !word.is_empty() {
+        res.push(word);
+    }
+    if res.is_empty() {
+        res.push("empty".to_string());
+    }
+    res
+}
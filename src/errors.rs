@@ -4,14 +4,9 @@ use std::fmt;
 pub struct Error(pub Loc, pub ErrorKind);
 
 // Errors implements Debug, it prints errors line by line
+#[derive(Default)]
 pub struct Errors(pub Vec<Error>);
 
-impl Default for Errors {
-    fn default() -> Self {
-        Self(vec![])
-    }
-}
-
 impl Errors {
     // can save some typing in checking the program
     // because when issuing an error, it often follows return a false / error type, which is the default
@@ -25,20 +20,190 @@ impl Errors {
         self.0.sort_unstable_by_key(|e| e.0);
         self
     }
+
+    pub fn has_error(&self) -> bool {
+        self.0.iter().any(|e| !e.1.is_warning())
+    }
+
+    // rustc style report of all errors, with source snippets from code
+    pub fn render(&self, file: &str, code: &str, color: bool) -> String {
+        let style = Style(color);
+        let mut res = String::new();
+        for e in &self.0 {
+            e.render(&mut res, file, code, style);
+        }
+        res
+    }
+
+    // print the report to stderr, colored if it is a terminal
+    pub fn report(&self, file: &str, code: &str) {
+        eprint!(
+            "{}",
+            self.render(file, code, atty::is(atty::Stream::Stderr))
+        );
+    }
+}
+
+// ansi escapes, or nothing when color is disabled
+#[derive(Copy, Clone)]
+struct Style(bool);
+
+impl Style {
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.0 {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const BLUE: &str = "1;34";
+const BOLD: &str = "1";
+
+impl Error {
+    fn render(&self, res: &mut String, file: &str, code: &str, style: Style) {
+        use std::fmt::Write;
+        let (level, color) = if self.1.is_warning() {
+            ("warning", YELLOW)
+        } else {
+            ("error", RED)
+        };
+        let _ = writeln!(
+            res,
+            "{}{}",
+            style.paint(color, level),
+            style.paint(BOLD, &format!(": {:?}", self.1))
+        );
+        let Loc(line, col) = self.0;
+        // gutter is as wide as the line number
+        let width = line.to_string().len();
+        let gutter = style.paint(BLUE, &format!("{:w$} |", "", w = width));
+        if self.0 != NO_LOC {
+            let _ = writeln!(
+                res,
+                "{:w$}{} {}:{}:{}",
+                "",
+                style.paint(BLUE, "-->"),
+                file,
+                line,
+                col,
+                w = width
+            );
+            if let Some(text) = code.lines().nth(line as usize - 1) {
+                // keep tabs so that the caret lines up with the source
+                let pad: String = text
+                    .chars()
+                    .take(col as usize - 1)
+                    .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let _ = writeln!(res, "{}", gutter);
+                let _ = writeln!(
+                    res,
+                    "{} {}",
+                    style.paint(BLUE, &format!("{} |", line)),
+                    text
+                );
+                let _ = writeln!(res, "{} {}{}", gutter, pad, style.paint(color, "^"));
+            }
+        }
+        for (label, text) in [("note", self.1.note()), ("help", self.1.help())].iter() {
+            if let Some(text) = text {
+                let _ = writeln!(
+                    res,
+                    "{:w$}{} {}: {}",
+                    "",
+                    style.paint(BLUE, " ="),
+                    style.paint(BOLD, label),
+                    text,
+                    w = width
+                );
+            }
+        }
+        res.push('\n');
+    }
 }
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = if self.1.is_warning() {
+            "Warning"
+        } else {
+            "Error"
+        };
         match self.0 {
-            NO_LOC => write!(f, "*** Error: {:?}", self.1),
-            loc => write!(f, "*** Error at {:?}: {:?}", loc, self.1),
+            NO_LOC => write!(f, "*** {}: {:?}", level, self.1),
+            loc => write!(f, "*** {} at {:?}: {:?}", level, loc, self.1),
         }
     }
 }
 
 pub enum ErrorKind {
     UnrecognizedChar(char),
-    SyntaxError,
+    // the token found and the tokens acceptable in its place
+    SyntaxError(String, Vec<String>),
+    UndefinedNonTerminal(String),
+    UnreachableRule(String),
+    // location of the first definition
+    DuplicateRule(String, Loc),
+    UnusedTerminal(String),
+    NonProductive(String),
+    EmptyClass(String),
+    // the minimum and maximum count
+    EmptyRepeat(usize, usize),
+    // the count as written
+    RepeatTooLarge(String),
+    UnterminatedComment,
+    UnsupportedException(String),
+    LeftRecursionKept(String),
+}
+
+impl ErrorKind {
+    // warnings don't stop further processing
+    pub fn is_warning(&self) -> bool {
+        use ErrorKind::*;
+        matches!(
+            self,
+            UnreachableRule(_)
+                | DuplicateRule(..)
+                | UnusedTerminal(_)
+                | UnsupportedException(_)
+                | LeftRecursionKept(_)
+        )
+    }
+
+    pub fn note(&self) -> Option<String> {
+        use ErrorKind::*;
+        match self {
+            NonProductive(_) => {
+                Some("every alternative refers to a non productive non terminal".to_string())
+            }
+            LeftRecursionKept(_) => Some("it never derives a terminal string".to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn help(&self) -> Option<String> {
+        use ErrorKind::*;
+        match self {
+            UnrecognizedChar('\'') | UnrecognizedChar('"') => Some(
+                "terminal is not closed or has an escape other than \\' \\\" \\\\ \\n \\r \\t \\u{..}"
+                    .to_string(),
+            ),
+            UndefinedNonTerminal(name) => Some(format!("add a rule `{} ::= ... ;`", name)),
+            UnreachableRule(_) => Some("reference it from the start rule or remove it".to_string()),
+            UnusedTerminal(_) => Some("remove it from the precedence declaration".to_string()),
+            UnsupportedException(_) => Some(
+                "only exceptions between sets of single characters become classes".to_string(),
+            ),
+            EmptyClass(_) => Some("ranges go from low to high, like 'a'..'z'".to_string()),
+            EmptyRepeat(min, max) => Some(format!("write the bounds as {{{},{}}}", max, min)),
+            LeftRecursionKept(name) => Some(format!("add an alternative not starting with {}", name)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for ErrorKind {
@@ -46,7 +211,34 @@ impl fmt::Debug for ErrorKind {
         use ErrorKind::*;
         match self {
             UnrecognizedChar(ch) => write!(f, "unrecognized character '{}'", ch),
-            SyntaxError => write!(f, "syntax error"),
+            SyntaxError(found, expected) => match expected.len() {
+                0 => write!(f, "syntax error at {}", found),
+                1 => write!(f, "expected {}, found {}", expected[0], found),
+                _ => write!(f, "expected one of {}, found {}", expected.join(" "), found),
+            },
+            UndefinedNonTerminal(name) => write!(f, "non terminal {} is not defined", name),
+            UnreachableRule(name) => write!(f, "rule {} is unreachable from start symbol", name),
+            DuplicateRule(name, loc) => write!(
+                f,
+                "rule {} is defined again, first definition is at {:?}",
+                name, loc
+            ),
+            UnusedTerminal(name) => write!(f, "terminal {} is declared but never used", name),
+            NonProductive(name) => write!(f, "rule {} never derives a terminal string", name),
+            EmptyClass(raw) => write!(f, "character class {} matches no character", raw),
+            EmptyRepeat(min, max) => write!(
+                f,
+                "repetition matches nothing, its minimum {} is above its maximum {}",
+                min, max
+            ),
+            RepeatTooLarge(count) => write!(f, "repetition count {} is too large", count),
+            UnterminatedComment => write!(f, "comment is not closed"),
+            UnsupportedException(raw) => write!(f, "exception {} is ignored", raw),
+            LeftRecursionKept(name) => write!(
+                f,
+                "left recursion of rule {} is kept, every alternative of it is left recursive",
+                name
+            ),
         }
     }
 }
@@ -1,4 +1,6 @@
 use crate::ast::*;
+use crate::errors::*;
+use crate::loc::Span;
 use std::cell::Cell;
 use typed_arena::Arena;
 
@@ -10,72 +12,145 @@ pub struct FlattenAlloc<'a> {
     counter: Cell<usize>,
 }
 
+impl<'a> FlattenAlloc<'a> {
+    // fresh non terminal name like name_opt0
+    pub fn fresh_name(&'a self, name: &str, kind: &str) -> &'a str {
+        let res = format!("{}_{}{}", name, kind, self.counter.get());
+        self.counter.set(self.counter.get() + 1);
+        self.string.alloc(res)
+    }
+}
+
 // returns new_name, new_prod, new_rules
+// new prods keep the span of the ebnf fragment they replace
 fn flatten_one<'a>(
     name: &'a str,
     prod: &'a Prod<'a>,
     alloc: &'a FlattenAlloc<'a>,
 ) -> (&'a str, &'a Prod<'a>, Vec<(&'a str, &'a Prod<'a>)>) {
     match prod {
-        Prod::Eps | Prod::Terminal(_) | Prod::NonTerminal(_) => (name, prod, vec![]),
-        Prod::Concat(l, r) => {
-            if let Prod::Eps = l {
+        Prod::Eps(_) | Prod::Terminal(..) | Prod::Class(..) | Prod::NonTerminal(..) => {
+            (name, prod, vec![])
+        }
+        Prod::Concat(l, r, span) => {
+            if let Prod::Eps(_) = l {
                 flatten_one(name, r, alloc)
-            } else if let Prod::Eps = r {
+            } else if let Prod::Eps(_) = r {
                 flatten_one(name, l, alloc)
             } else {
                 let (new_name, ll, l_res) = flatten_one(name, l, alloc);
-                if l_res.len() > 0 {
-                    (new_name, alloc.prod.alloc(Prod::Concat(ll, r)), l_res)
+                if !l_res.is_empty() {
+                    (
+                        new_name,
+                        alloc.prod.alloc(Prod::Concat(ll, r, *span)),
+                        l_res,
+                    )
                 } else {
                     let (new_name, rr, r_res) = flatten_one(name, r, alloc);
-                    (new_name, alloc.prod.alloc(Prod::Concat(l, rr)), r_res)
+                    (
+                        new_name,
+                        alloc.prod.alloc(Prod::Concat(l, rr, *span)),
+                        r_res,
+                    )
                 }
             }
         }
-        Prod::Optional(o) => {
-            let orig_name = format!("{}", name);
-            let orig_name = alloc.string.alloc(orig_name);
-            let opt_name = format!("{}_opt{}", name, alloc.counter.get());
-            alloc.counter.set(alloc.counter.get() + 1);
-            let opt_name = alloc.string.alloc(opt_name);
-            let opt = alloc.prod.alloc(Prod::NonTerminal(opt_name));
-            (orig_name, opt, vec![(opt_name, o), (opt_name, &Prod::Eps)])
-        }
-        Prod::Star(o) => {
-            let orig_name = format!("{}", name);
-            let orig_name = alloc.string.alloc(orig_name);
-            let star_name = format!("{}_star{}", name, alloc.counter.get());
-            alloc.counter.set(alloc.counter.get() + 1);
-            let star_name = alloc.string.alloc(star_name);
-            let star = alloc.prod.alloc(Prod::NonTerminal(star_name));
-            let concat = alloc.prod.alloc(Prod::Concat(o, star));
-            (
-                orig_name,
-                star,
-                vec![(star_name, concat), (star_name, &Prod::Eps)],
-            )
+        Prod::Optional(o, span) => {
+            let orig_name = alloc.string.alloc(name.to_string());
+            let opt_name = alloc.fresh_name(name, "opt");
+            let opt = alloc.prod.alloc(Prod::NonTerminal(opt_name, *span));
+            let eps = alloc.prod.alloc(Prod::Eps(*span));
+            (orig_name, opt, vec![(opt_name, o), (opt_name, eps)])
+        }
+        Prod::Star(o, span) => {
+            let orig_name = alloc.string.alloc(name.to_string());
+            let star_name = alloc.fresh_name(name, "star");
+            let star = alloc.prod.alloc(Prod::NonTerminal(star_name, *span));
+            let concat = alloc.prod.alloc(Prod::Concat(o, star, *span));
+            let eps = alloc.prod.alloc(Prod::Eps(*span));
+            (orig_name, star, vec![(star_name, concat), (star_name, eps)])
+        }
+        Prod::Repeat(o, min, max, span) => {
+            let orig_name = alloc.string.alloc(name.to_string());
+            let rep_name = alloc.fresh_name(name, "rep");
+            let rep = alloc.prod.alloc(Prod::NonTerminal(rep_name, *span));
+            let mut new_rules = vec![];
+            // copies refer to a single symbol, so o is flattened only once
+            let item = match o {
+                Prod::Terminal(..) | Prod::Class(..) | Prod::NonTerminal(..) => *o,
+                _ => {
+                    let item_name = alloc.fresh_name(name, "group");
+                    match o {
+                        Prod::Alt(alts, _) => {
+                            new_rules.extend(alts.iter().map(|alt| (item_name, *alt)))
+                        }
+                        _ => new_rules.push((item_name, *o)),
+                    }
+                    alloc.prod.alloc(Prod::NonTerminal(item_name, *span))
+                }
+            };
+            // the optional copies nest like (o (o o?)?)?, one rule per level
+            let mut tail: Option<&Prod> = match max {
+                None => Some(alloc.prod.alloc(Prod::Star(item, *span))),
+                Some(max) if max > min => {
+                    let mut tail: &Prod = alloc.prod.alloc(Prod::Optional(item, *span));
+                    for _ in min + 1..*max {
+                        let concat = alloc.prod.alloc(Prod::Concat(item, tail, *span));
+                        tail = alloc.prod.alloc(Prod::Optional(concat, *span));
+                    }
+                    Some(tail)
+                }
+                // exactly min copies, with no eps after them
+                Some(_) => None,
+            };
+            for _ in 0..*min {
+                tail = Some(match tail {
+                    Some(tail) => alloc.prod.alloc(Prod::Concat(item, tail, *span)),
+                    None => item,
+                });
+            }
+            let tail = tail.unwrap_or_else(|| alloc.prod.alloc(Prod::Eps(*span)));
+            new_rules.push((rep_name, tail));
+            (orig_name, rep, new_rules)
+        }
+        Prod::Alt(alts, span) => {
+            let orig_name = alloc.string.alloc(name.to_string());
+            let group_name = alloc.fresh_name(name, "group");
+            let group = alloc.prod.alloc(Prod::NonTerminal(group_name, *span));
+            let new_rules = alts.iter().map(|alt| (&*group_name, *alt)).collect();
+            (orig_name, group, new_rules)
         }
     }
 }
 
 fn flatten_prod<'a>(prod: &'a Prod<'a>, alloc: &'a FlattenAlloc<'a>) -> Vec<&'a FlatProd<'a>> {
     match prod {
-        Prod::Concat(l, r) => {
+        Prod::Concat(l, r, _) => {
             let mut l_res = flatten_prod(l, alloc);
             let mut r_res = flatten_prod(r, alloc);
             l_res.append(&mut r_res);
             l_res
         }
-        Prod::Terminal(t) => {
-            let p = alloc.flat_prod.alloc(FlatProd::Terminal(t));
+        Prod::Terminal(lit, _) => {
+            // terminals are identified by their value
+            let name = terminal_name(lit.value);
+            let name = if name == lit.raw {
+                lit.raw
+            } else {
+                alloc.string.alloc(name)
+            };
+            let p = alloc.flat_prod.alloc(FlatProd::Terminal(name));
+            vec![p]
+        }
+        Prod::Class(class, _) => {
+            let p = alloc.flat_prod.alloc(FlatProd::Class(class));
             vec![p]
         }
-        Prod::NonTerminal(t) => {
+        Prod::NonTerminal(t, _) => {
             let p = alloc.flat_prod.alloc(FlatProd::NonTerminal(t));
             vec![p]
         }
-        Prod::Eps => {
+        Prod::Eps(_) => {
             let p = alloc.flat_prod.alloc(FlatProd::Eps);
             vec![p]
         }
@@ -86,7 +161,8 @@ fn flatten_prod<'a>(prod: &'a Prod<'a>, alloc: &'a FlattenAlloc<'a>) -> Vec<&'a
 fn flatten_rule<'a>(rule: RuleDef<'a>, alloc: &'a FlattenAlloc<'a>) -> FlatRuleDef<'a> {
     FlatRuleDef {
         name: rule.name,
-        prod: flatten_prod(&rule.prod[0], alloc),
+        span: rule.span,
+        prod: flatten_prod(rule.prod[0], alloc),
     }
 }
 
@@ -99,11 +175,13 @@ pub fn flatten<'a>(file: &'a File<'a>, alloc: &'a FlattenAlloc<'a>) -> Vec<FlatR
                 let (new_name, res_prod, new) = flatten_one(rule.name, prod, alloc);
                 new_rules.push(RuleDef {
                     name: new_name,
+                    span: prod.span(),
                     prod: vec![res_prod],
                 });
                 for (name, new_rule) in new {
                     new_rules.push(RuleDef {
                         name,
+                        span: new_rule.span(),
                         prod: vec![new_rule],
                     });
                 }
@@ -120,3 +198,356 @@ pub fn flatten<'a>(file: &'a File<'a>, alloc: &'a FlattenAlloc<'a>) -> Vec<FlatR
         .map(|rule| flatten_rule(rule, alloc))
         .collect()
 }
+
+// alternative of a grouped rule along with the span it comes from
+type Alt<'a> = (FlatProds<'a>, Span);
+
+// rules grouped by non terminal in order of first definition, eps is an empty alternative
+fn group_rules<'a>(rules: &[FlatRuleDef<'a>]) -> Vec<(&'a str, Vec<Alt<'a>>)> {
+    let mut res: Vec<(&'a str, Vec<Alt<'a>>)> = vec![];
+    for rule in rules.iter() {
+        let alt: FlatProds<'a> = rule.prod.iter().filter(|p| !p.is_eps()).cloned().collect();
+        match res.iter_mut().find(|(name, _)| *name == rule.name) {
+            Some((_, alts)) => alts.push((alt, rule.span)),
+            None => res.push((rule.name, vec![(alt, rule.span)])),
+        }
+    }
+    res
+}
+
+fn ungroup_rules<'a>(
+    groups: Vec<(&'a str, Vec<Alt<'a>>)>,
+    alloc: &'a FlattenAlloc<'a>,
+) -> Vec<FlatRuleDef<'a>> {
+    let mut res = vec![];
+    for (name, alts) in groups.into_iter() {
+        for (alt, span) in alts.into_iter() {
+            let prod = if alt.is_empty() {
+                vec![&*alloc.flat_prod.alloc(FlatProd::Eps)]
+            } else {
+                alt
+            };
+            res.push(FlatRuleDef { name, span, prod });
+        }
+    }
+    res
+}
+
+// remove direct and indirect left recursion with the standard ordering algorithm
+// the grammar should have no cycles (A =>+ A) and no nullable left corners
+// non terminals whose alternatives are all left recursive are reported and kept
+pub fn eliminate_left_recursion<'a>(
+    rules: &[FlatRuleDef<'a>],
+    alloc: &'a FlattenAlloc<'a>,
+) -> (Vec<FlatRuleDef<'a>>, Errors) {
+    let mut errors = Errors::default();
+    let mut groups = group_rules(rules);
+    let mut tails: Vec<Option<(&'a str, Vec<Alt<'a>>)>> = vec![None; groups.len()];
+    for i in 0..groups.len() {
+        let name = groups[i].0;
+        // substitute A_i ::= A_j gamma with all A_j rules for j < i
+        for j in 0..i {
+            let (prev, alts_j) = (groups[j].0, groups[j].1.clone());
+            let mut new_alts: Vec<Alt<'a>> = vec![];
+            for (alt, span) in groups[i].1.drain(..) {
+                if alt
+                    .first()
+                    .is_some_and(|p| **p == FlatProd::NonTerminal(prev))
+                {
+                    for (delta, _) in alts_j.iter() {
+                        let mut new_alt = delta.clone();
+                        new_alt.extend(alt[1..].iter().cloned());
+                        if !new_alts.iter().any(|(a, _)| *a == new_alt) {
+                            new_alts.push((new_alt, span));
+                        }
+                    }
+                } else if !new_alts.iter().any(|(a, _)| *a == alt) {
+                    new_alts.push((alt, span));
+                }
+            }
+            groups[i].1 = new_alts;
+        }
+
+        // A ::= A alpha | beta => A ::= beta A_tail, A_tail ::= alpha A_tail | _
+        let (recursive, rest): (Vec<Alt<'a>>, Vec<Alt<'a>>) =
+            groups[i].1.drain(..).partition(|(alt, _)| {
+                alt.first()
+                    .is_some_and(|p| **p == FlatProd::NonTerminal(name))
+            });
+        if recursive.is_empty() {
+            groups[i].1 = rest;
+            continue;
+        }
+        if rest.is_empty() {
+            // no alternative ends the recursion, so A derives no terminal string
+            errors.issue::<()>(
+                recursive[0].1.start,
+                ErrorKind::LeftRecursionKept(name.to_string()),
+            );
+            groups[i].1 = recursive;
+            continue;
+        }
+        let tail_name = alloc.fresh_name(name, "tail");
+        let tail = &*alloc.flat_prod.alloc(FlatProd::NonTerminal(tail_name));
+        let tail_span = recursive[0].1;
+        groups[i].1 = rest
+            .into_iter()
+            .map(|(mut beta, span)| {
+                beta.push(tail);
+                (beta, span)
+            })
+            .collect();
+        let mut tail_alts: Vec<Alt<'a>> = recursive
+            .into_iter()
+            .filter(|(alt, _)| alt.len() > 1) // A ::= A is useless
+            .map(|(alt, span)| {
+                let mut alpha = alt[1..].to_vec();
+                alpha.push(tail);
+                (alpha, span)
+            })
+            .collect();
+        tail_alts.push((vec![], tail_span));
+        tails[i] = Some((tail_name, tail_alts));
+    }
+
+    let mut res = vec![];
+    for (group, tail) in groups.into_iter().zip(tails) {
+        res.push(group);
+        res.extend(tail);
+    }
+    (ungroup_rules(res, alloc), errors)
+}
+
+// factor alternatives of the same non terminal sharing a common prefix into a fresh non terminal
+// A ::= x y | x z => A ::= x A_fact, A_fact ::= y | z, until no alternatives share a prefix
+pub fn left_factor<'a>(
+    rules: &[FlatRuleDef<'a>],
+    alloc: &'a FlattenAlloc<'a>,
+) -> Vec<FlatRuleDef<'a>> {
+    let mut groups = group_rules(rules);
+    let mut i = 0;
+    while i < groups.len() {
+        let alts = &groups[i].1;
+        let shared = alts.iter().enumerate().find_map(|(k, (alt, _))| {
+            alt.first()
+                .filter(|first| {
+                    alts[k + 1..]
+                        .iter()
+                        .any(|(other, _)| other.first() == Some(first))
+                })
+                .cloned()
+        });
+        let first = match shared {
+            Some(first) => first,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let factored: Vec<&Alt<'a>> = alts
+            .iter()
+            .filter(|(alt, _)| alt.first() == Some(&first))
+            .collect();
+        let mut prefix_len = 1;
+        while factored
+            .iter()
+            .all(|(alt, _)| alt.len() > prefix_len && alt[prefix_len] == factored[0].0[prefix_len])
+        {
+            prefix_len += 1;
+        }
+        let prefix = factored[0].0[..prefix_len].to_vec();
+        // equal tails, like two empty ones, would be a conflict of their own
+        let mut suffixes: Vec<Alt<'a>> = vec![];
+        for (alt, span) in factored.iter() {
+            let suffix = alt[prefix_len..].to_vec();
+            if !suffixes.iter().any(|(other, _)| *other == suffix) {
+                suffixes.push((suffix, *span));
+            }
+        }
+
+        let name = alloc.fresh_name(groups[i].0, "fact");
+        let fact = &*alloc.flat_prod.alloc(FlatProd::NonTerminal(name));
+        let mut new_alts = vec![];
+        let mut inserted = false;
+        for (alt, span) in groups[i].1.drain(..) {
+            if alt.first() != Some(&first) {
+                new_alts.push((alt, span));
+            } else if !inserted {
+                let mut new_alt = prefix.clone();
+                new_alt.push(fact);
+                new_alts.push((new_alt, span));
+                inserted = true;
+            }
+        }
+        groups[i].1 = new_alts;
+        // the new rules may need factoring as well, so they are checked later
+        groups.insert(i + 1, (name, suffixes));
+    }
+    ungroup_rules(groups, alloc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::work;
+
+    // flattened rules of code, which must have no errors, through pass
+    fn with_rules(
+        code: &str,
+        pass: impl for<'a> FnOnce(&[FlatRuleDef<'a>], &'a FlattenAlloc<'a>) -> Vec<FlatRuleDef<'a>>,
+    ) -> Vec<String> {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        let res = pass(&rules, &flatten_alloc);
+        res.iter().map(|rule| rule.to_string()).collect()
+    }
+
+    fn flattened(code: &str) -> Vec<String> {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let res = flatten(file, &flatten_alloc);
+        res.iter().map(|rule| rule.to_string()).collect()
+    }
+
+    #[test]
+    fn eliminate_left_recursion_adds_tails() {
+        let res = with_rules(include_str!("../lr.ebnf"), |rules, alloc| {
+            let (res, errors) = eliminate_left_recursion(rules, alloc);
+            assert!(errors.0.is_empty());
+            res
+        });
+        assert_eq!(
+            res,
+            vec![
+                "S ::= E",
+                "E ::= T E_tail0",
+                "E_tail0 ::= '+' T E_tail0",
+                "E_tail0 ::= _",
+                "T ::= '(' E ')' T_tail1",
+                "T ::= F T_tail1",
+                "T_tail1 ::= '*' F T_tail1",
+                "T_tail1 ::= _",
+                "F ::= 'd'",
+                "F ::= 'v'",
+            ]
+        );
+        // B ::= A 'b' is left recursive through A
+        let res = with_rules(
+            "S ::= A; A ::= B 'a' | 'c'; B ::= A 'b' | 'd';",
+            |rules, alloc| eliminate_left_recursion(rules, alloc).0,
+        );
+        assert_eq!(
+            res,
+            vec![
+                "S ::= A",
+                "A ::= 'c'",
+                "A ::= B 'a'",
+                "B ::= 'd' B_tail0",
+                "B ::= 'c' 'b' B_tail0",
+                "B_tail0 ::= 'a' 'b' B_tail0",
+                "B_tail0 ::= _",
+            ]
+        );
+        // A has no alternative ending the recursion
+        let res = with_rules("S ::= A; A ::= A 'x';", |rules, alloc| {
+            let (res, errors) = eliminate_left_recursion(rules, alloc);
+            assert!(!errors.has_error());
+            assert_eq!(errors.0.len(), 1);
+            res
+        });
+        assert_eq!(res, vec!["S ::= A", "A ::= A 'x'"]);
+    }
+
+    #[test]
+    fn left_factor_shares_prefixes() {
+        let res = with_rules(
+            "S ::= A; A ::= 'x' 'y' | 'x' 'z' | 'x' | 'x' | 'w';",
+            left_factor,
+        );
+        // 'x' twice gives a single empty tail
+        assert_eq!(
+            res,
+            vec![
+                "S ::= A",
+                "A ::= 'w'",
+                "A ::= 'x' A_fact0",
+                "A_fact0 ::= _",
+                "A_fact0 ::= 'z'",
+                "A_fact0 ::= 'y'",
+            ]
+        );
+    }
+
+    #[test]
+    fn flattened_rules_keep_spans() {
+        let alloc = ASTAlloc::default();
+        let (file, _) = work("S ::= 'a'\n  'b'?;", &alloc);
+        let flatten_alloc = FlattenAlloc::default();
+        let spans: Vec<String> = flatten(file, &flatten_alloc)
+            .iter()
+            .map(|rule| {
+                format!(
+                    "{} {:?} {}..{}",
+                    rule, rule.span, rule.span.lo, rule.span.hi
+                )
+            })
+            .collect();
+        assert_eq!(format!("{:?}", file.rules[0].span), "(1,1)-(2,8)");
+        // the optional rules point at 'b'?
+        assert_eq!(
+            spans,
+            vec![
+                "S ::= 'a' S_opt0 (1,7)-(2,7) 6..16",
+                "S_opt0 ::= 'b' (2,3)-(2,6) 12..15",
+                "S_opt0 ::= _ (2,3)-(2,7) 12..16",
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_become_rules() {
+        let res = flattened("S ::= ('+' | '-' 'x'?) 'n';");
+        assert_eq!(
+            res,
+            vec![
+                "S ::= S_group0 'n'",
+                "S_group0 ::= '+'",
+                "S_group0 ::= '-' S_group0_opt1",
+                "S_group0_opt1 ::= 'x'",
+                "S_group0_opt1 ::= _",
+            ]
+        );
+    }
+
+    #[test]
+    fn bounded_repetitions_unroll() {
+        assert_eq!(
+            flattened("S ::= 'a'{2,3};"),
+            vec![
+                "S ::= S_rep0",
+                "S_rep0 ::= 'a' 'a' S_rep0_opt1",
+                "S_rep0_opt1 ::= 'a'",
+                "S_rep0_opt1 ::= _",
+            ]
+        );
+        assert_eq!(
+            flattened("S ::= 'a'{2};"),
+            vec!["S ::= S_rep0", "S_rep0 ::= 'a' 'a'"]
+        );
+        assert_eq!(
+            flattened("S ::= 'a'{1,};"),
+            vec![
+                "S ::= S_rep0",
+                "S_rep0 ::= 'a' S_rep0_star1",
+                "S_rep0_star1 ::= 'a' S_rep0_star1",
+                "S_rep0_star1 ::= _",
+            ]
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::*;
+
+// dialect of the grammar file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ebnf,
+    Iso,
+    W3c,
+    Abnf,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["ebnf", "iso", "w3c", "abnf"];
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "ebnf" => Some(Format::Ebnf),
+            "iso" => Some(Format::Iso),
+            "w3c" => Some(Format::W3c),
+            "abnf" => Some(Format::Abnf),
+            _ => None,
+        }
+    }
+
+    // by file extension, the crate's own ebnf by default
+    pub fn from_path(path: &str) -> Format {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+        match ext {
+            "iso" | "iebnf" => Format::Iso,
+            "w3c" | "xebnf" => Format::W3c,
+            "abnf" => Format::Abnf,
+            _ => Format::Ebnf,
+        }
+    }
+
+    pub fn parse<'p>(self, code: &'p str, alloc: &'p ASTAlloc<'p>) -> (&'p File<'p>, Errors) {
+        match self {
+            Format::Ebnf => work(code, alloc),
+            Format::Iso => parse_iso(code, alloc),
+            Format::W3c => parse_w3c(code, alloc),
+            Format::Abnf => parse_abnf(code, alloc),
+        }
+    }
+
+    // parse and check code read from path, reporting diagnostics to stderr
+    // the file keeps the rules that parsed, the flag tells whether there were errors
+    pub fn load<'p>(
+        self,
+        path: &str,
+        code: &'p str,
+        alloc: &'p ASTAlloc<'p>,
+    ) -> (&'p File<'p>, bool) {
+        let (file, mut errors) = self.parse(code, alloc);
+        errors.0.extend(check(file).0);
+        let errors = errors.sorted();
+        errors.report(path, code);
+        (file, errors.has_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // flattened rules of code, sorted as the dialects keep alternatives in different orders
+    fn flattened(format: Format, code: &str) -> Vec<String> {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = format.parse(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let mut res: Vec<String> = flatten(file, &flatten_alloc)
+            .iter()
+            .map(|rule| rule.to_string())
+            .collect();
+        res.sort();
+        res
+    }
+
+    fn messages(format: Format, code: &str) -> Vec<String> {
+        let alloc = ASTAlloc::default();
+        let (_, errors) = format.parse(code, &alloc);
+        errors.0.iter().map(|e| format!("{:?}", e)).collect()
+    }
+
+    #[test]
+    fn iso_agrees_with_ebnf() {
+        let ebnf = flattened(
+            Format::Ebnf,
+            "S ::= 'a' B* C?; B ::= 'b' | 'c' 'c'; C ::= 'd';",
+        );
+        let iso = "(* comment *) S = 'a', {B}, [C]; B = 'b' | 'c', 'c'; C = 'd';";
+        assert_eq!(flattened(Format::Iso, iso), ebnf);
+        let iso = "S = 'a', (:B:), (/C/). B = 'b' / 'c', 'c'. C = 'd'.";
+        assert_eq!(flattened(Format::Iso, iso), ebnf);
+    }
+
+    #[test]
+    fn iso_counts_repetitions() {
+        assert_eq!(
+            flattened(Format::Iso, "S = 2 * 'a';"),
+            vec!["S ::= S_rep0", "S_rep0 ::= 'a' 'a'"]
+        );
+        let found = messages(Format::Iso, "S = 99999999999999999999 * 'a';");
+        assert_eq!(found.len(), 1);
+        assert!(found[0].contains("smaller repetition count"), "{:?}", found);
+    }
+
+    #[test]
+    fn w3c_agrees_with_ebnf() {
+        let ebnf = flattened(
+            Format::Ebnf,
+            "S ::= 'a' B* C?; B ::= 'b' | 'c' 'c'; C ::= 'd';",
+        );
+        let w3c = "S ::= 'a' B* C?\nB ::= 'b' | 'c' 'c'\nC ::= 'd'\n";
+        assert_eq!(flattened(Format::W3c, w3c), ebnf);
+        let w3c = "[1] S ::= 'a' B* C? /* comment */\n[2] B ::= 'b' | 'c' 'c' [ wfc: Twice ]\n[3] C ::= 'd'\n";
+        assert_eq!(flattened(Format::W3c, w3c), ebnf);
+    }
+
+    #[test]
+    fn w3c_reads_characters_and_exceptions() {
+        assert_eq!(
+            flattened(Format::W3c, "S ::= #x41 [^#x30-#x39] [a-z] - 'x'\n"),
+            vec!["S ::= 'A' [^#x30-#x39] [a-z] - 'x'"]
+        );
+        // a difference of strings is no class, only its left side is kept
+        assert_eq!(
+            messages(Format::W3c, "S ::= A - 'x'\nA ::= 'a' 'b'\n"),
+            vec!["*** Warning at (1,7): exception A - 'x' is ignored"]
+        );
+        assert_eq!(
+            messages(Format::W3c, "S ::= #xZZ\n"),
+            vec!["*** Error at (1,9): expected hexadecimal code point, found `Z`"]
+        );
+    }
+
+    #[test]
+    fn abnf_agrees_with_ebnf() {
+        let ebnf = flattened(
+            Format::Ebnf,
+            "S ::= 'a' B* C?; B ::= 'b' | 'c' 'c'; C ::= 'd';",
+        );
+        let abnf = "S = %s\"a\" *B [C]\nB = %s\"b\" / %s\"c\" %x63 ; comment\nC = %d100\n";
+        assert_eq!(flattened(Format::Abnf, abnf), ebnf);
+    }
+
+    #[test]
+    fn abnf_strings_ignore_case() {
+        assert_eq!(
+            flattened(Format::Abnf, "S = \"if(x)\" %i\"B\" %s\"If\" \"-1\"\n"),
+            vec!["S ::= [iI] [fF] '(' [xX] ')' [bB] 'If' '-1'"]
+        );
+    }
+
+    #[test]
+    fn abnf_counts_repetitions() {
+        assert_eq!(
+            flattened(Format::Abnf, "S = 2*3%s\"a\" *%s\"b\" 2%s\"c\"\n"),
+            vec![
+                "S ::= S_rep0 S_star1 S_rep3",
+                "S_rep0 ::= 'a' 'a' S_rep0_opt2",
+                "S_rep0_opt2 ::= 'a'",
+                "S_rep0_opt2 ::= _",
+                "S_rep3 ::= 'c' 'c'",
+                "S_star1 ::= 'b' S_star1",
+                "S_star1 ::= _",
+            ]
+        );
+        let found = messages(Format::Abnf, "S = 1*99999999999999999999%s\"a\"\n");
+        assert_eq!(found.len(), 1);
+        assert!(found[0].contains("smaller repetition count"), "{:?}", found);
+    }
+}
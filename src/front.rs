@@ -0,0 +1,358 @@
+// shared pieces of the hand written front-ends for other ebnf dialects
+use crate::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+// cursor over source code, positions are byte offsets
+pub struct Scanner<'a> {
+    pub code: &'a str,
+    pub pos: usize,
+    // none for builtin code, whose nodes have no location
+    lines: Option<Lines>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(code: &'a str) -> Scanner<'a> {
+        Scanner {
+            code,
+            pos: 0,
+            lines: Some(Lines::new(code)),
+        }
+    }
+
+    pub fn builtin(code: &'a str) -> Scanner<'a> {
+        Scanner {
+            code,
+            pos: 0,
+            lines: None,
+        }
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.code[self.pos..]
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    pub fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    // consume s if the code continues with it
+    pub fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    // consume characters while f holds, returning them
+    pub fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let lo = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+        &self.code[lo..self.pos]
+    }
+
+    pub fn loc(&self, offset: usize) -> Loc {
+        match &self.lines {
+            Some(lines) => lines.loc(self.code, offset),
+            None => NO_LOC,
+        }
+    }
+
+    pub fn span(&self, lo: usize, hi: usize) -> Span {
+        if self.lines.is_none() {
+            return NO_SPAN;
+        }
+        Span {
+            start: self.loc(lo),
+            end: self.loc(hi),
+            lo,
+            hi,
+        }
+    }
+
+    // what is found at the cursor, for syntax errors
+    pub fn found(&self) -> String {
+        match self.peek() {
+            Some(ch) => format!("`{}`", ch),
+            None => "end of file".to_string(),
+        }
+    }
+
+    pub fn error(&self, errors: &mut Errors, expected: &[&str]) {
+        let expected = expected.iter().map(|e| e.to_string()).collect();
+        errors.issue(
+            self.loc(self.pos),
+            ErrorKind::SyntaxError(self.found(), expected),
+        )
+    }
+
+    // digits of a repetition count, none if there are none
+    // a count too large for a usize is a syntax error
+    pub fn count(&mut self, errors: &mut Errors) -> Option<Option<usize>> {
+        let lo = self.pos;
+        let digits = self.take_while(|ch| ch.is_ascii_digit());
+        if digits.is_empty() {
+            return Some(None);
+        }
+        match digits.parse() {
+            Ok(count) => Some(Some(count)),
+            Err(_) => {
+                self.pos = lo;
+                self.error(errors, &["smaller repetition count"]);
+                None
+            }
+        }
+    }
+}
+
+// file with the mapping from names to rules filled
+pub fn make_file<'a>(
+    alloc: &'a ASTAlloc<'a>,
+    rules: Vec<RuleDef<'a>>,
+    broken: Vec<&'a str>,
+) -> &'a File<'a> {
+    let file = alloc.file.alloc(File {
+        rules,
+        precs: vec![],
+        broken,
+        mapping: RefCell::new(BTreeMap::new()),
+    });
+    let mut mapping = file.mapping.borrow_mut();
+    for rule in file.rules.iter() {
+        mapping.insert(rule.name, rule);
+    }
+    drop(mapping);
+    file
+}
+
+// the only alternative, or all of them as a group
+pub fn make_alt<'a>(
+    alloc: &'a ASTAlloc<'a>,
+    mut alts: Vec<&'a Prod<'a>>,
+    span: Span,
+) -> &'a Prod<'a> {
+    if alts.len() == 1 {
+        alts.pop().unwrap()
+    } else {
+        alloc.prod.alloc(Prod::Alt(alts, span))
+    }
+}
+
+// concatenation of the productions, eps if there is none
+pub fn make_concat<'a>(
+    alloc: &'a ASTAlloc<'a>,
+    prods: Vec<&'a Prod<'a>>,
+    span: Span,
+) -> &'a Prod<'a> {
+    let mut prods = prods.into_iter();
+    match prods.next() {
+        Some(first) => prods.fold(first, |l, r| {
+            alloc.prod.alloc(Prod::Concat(l, r, l.span().to(r.span())))
+        }),
+        None => alloc.prod.alloc(Prod::Eps(span)),
+    }
+}
+
+// p repeated at least min and at most max times, unbounded without max
+// the counts that have an operator of their own use it
+pub fn make_repeat<'a>(
+    alloc: &'a ASTAlloc<'a>,
+    p: &'a Prod<'a>,
+    min: usize,
+    max: Option<usize>,
+    span: Span,
+) -> &'a Prod<'a> {
+    match (min, max) {
+        (0, None) => alloc.prod.alloc(Prod::Star(p, span)),
+        (0, Some(1)) => alloc.prod.alloc(Prod::Optional(p, span)),
+        (1, Some(1)) => p,
+        _ => alloc.prod.alloc(Prod::Repeat(p, min, max, span)),
+    }
+}
+
+const MAX_CHAR: u32 = 0x10FFFF;
+
+// sorted and merged inclusive ranges
+fn normalize(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges.sort();
+    let mut res: Vec<(u32, u32)> = vec![];
+    for (lo, hi) in ranges {
+        match res.last_mut() {
+            Some(last) if lo <= last.1.saturating_add(1) => last.1 = last.1.max(hi),
+            _ => res.push((lo, hi)),
+        }
+    }
+    res
+}
+
+fn complement(ranges: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut res = vec![];
+    let mut next = 0;
+    for (lo, hi) in ranges {
+        if *lo > next {
+            res.push((next, lo - 1));
+        }
+        next = hi + 1;
+    }
+    if next <= MAX_CHAR {
+        res.push((next, MAX_CHAR));
+    }
+    res
+}
+
+// rules by name, with all their alternatives
+pub type Defs<'a> = BTreeMap<&'a str, Vec<&'a Prod<'a>>>;
+
+pub fn defs<'a>(rules: &[RuleDef<'a>]) -> Defs<'a> {
+    let mut res: Defs = BTreeMap::new();
+    for rule in rules.iter() {
+        res.entry(rule.name)
+            .or_default()
+            .extend(rule.prod.iter().cloned());
+    }
+    res
+}
+
+// characters matched by a class, a single character terminal, or alternatives of them
+fn char_set<'a>(
+    prod: &Prod<'a>,
+    defs: &Defs<'a>,
+    visiting: &mut Vec<&'a str>,
+) -> Option<Vec<(u32, u32)>> {
+    match prod {
+        Prod::Class(class, _) => {
+            let ranges = normalize(
+                class
+                    .ranges
+                    .iter()
+                    .map(|(lo, hi)| (*lo as u32, *hi as u32))
+                    .collect(),
+            );
+            Some(if class.negated {
+                complement(&ranges)
+            } else {
+                ranges
+            })
+        }
+        Prod::Terminal(lit, _) => {
+            let mut chars = lit.value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Some(vec![(ch as u32, ch as u32)]),
+                _ => None,
+            }
+        }
+        Prod::Alt(alts, _) => alts_set(alts, defs, visiting),
+        Prod::NonTerminal(name, _) if !visiting.contains(name) => {
+            visiting.push(name);
+            let res = alts_set(defs.get(name)?, defs, visiting);
+            visiting.pop();
+            res
+        }
+        _ => None,
+    }
+}
+
+fn alts_set<'a>(
+    alts: &[&Prod<'a>],
+    defs: &Defs<'a>,
+    visiting: &mut Vec<&'a str>,
+) -> Option<Vec<(u32, u32)>> {
+    let mut res = vec![];
+    for alt in alts.iter() {
+        res.extend(char_set(alt, defs, visiting)?);
+    }
+    Some(normalize(res))
+}
+
+// a - b as a character class named raw, when both sides are character sets
+pub fn exception<'a>(
+    a: &Prod<'a>,
+    b: &Prod<'a>,
+    defs: &Defs<'a>,
+    raw: &'a str,
+) -> Option<CharClass<'a>> {
+    let a = char_set(a, defs, &mut vec![])?;
+    let b = char_set(b, defs, &mut vec![])?;
+    // a - b = a & !b = !(!a | b)
+    let mut ranges = complement(&a);
+    ranges.extend(b);
+    Some(CharClass {
+        raw,
+        negated: false,
+        ranges: to_chars(complement(&normalize(ranges))),
+    })
+}
+
+fn to_chars(ranges: Vec<(u32, u32)>) -> Vec<(char, char)> {
+    ranges
+        .into_iter()
+        .filter_map(|(lo, hi)| {
+            // surrogates are not chars
+            let lo = if (0xD800..0xE000).contains(&lo) {
+                0xE000
+            } else {
+                lo
+            };
+            let hi = if (0xD800..0xE000).contains(&hi) {
+                0xD7FF
+            } else {
+                hi
+            };
+            Some((std::char::from_u32(lo)?, std::char::from_u32(hi)?)).filter(|(lo, hi)| lo <= hi)
+        })
+        .collect()
+}
+
+// the characters a negated class matches, as sorted ranges
+pub fn complement_ranges(class: &CharClass) -> Vec<(char, char)> {
+    let ranges = class
+        .ranges
+        .iter()
+        .map(|(lo, hi)| (*lo as u32, *hi as u32))
+        .collect();
+    to_chars(complement(&normalize(ranges)))
+}
+
+// exceptions may refer to rules defined later, so when pass reports one
+// it runs again with the rules of the first pass to resolve them
+pub fn resolve_exceptions<'a>(
+    alloc: &'a ASTAlloc<'a>,
+    pass: impl Fn(Defs<'a>) -> (Vec<RuleDef<'a>>, Vec<&'a str>, Errors, bool),
+) -> (&'a File<'a>, Errors) {
+    let (mut rules, mut broken, mut errors, has_exception) = pass(Defs::new());
+    if has_exception {
+        let (new_rules, new_broken, new_errors, _) = pass(defs(&rules));
+        rules = new_rules;
+        broken = new_broken;
+        errors = new_errors;
+    }
+    (make_file(alloc, rules, broken), errors.sorted())
+}
+
+// a - b as a class, or a alone with a warning when it can't be expressed
+pub fn make_exception<'a>(
+    alloc: &'a ASTAlloc<'a>,
+    errors: &mut Errors,
+    defs: &Defs<'a>,
+    (a, b): (&'a Prod<'a>, &'a Prod<'a>),
+    raw: &'a str,
+    span: Span,
+) -> &'a Prod<'a> {
+    match exception(a, b, defs, raw) {
+        Some(class) => alloc.prod.alloc(Prod::Class(class, span)),
+        None => {
+            errors.issue::<()>(span.start, ErrorKind::UnsupportedException(raw.to_string()));
+            a
+        }
+    }
+}
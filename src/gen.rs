@@ -2,7 +2,7 @@ use super::*;
 use rand::random;
 
 pub trait Generate {
-    fn generate<'a>(&self, file: &File, max_level: usize) -> String;
+    fn generate(&self, file: &File, max_level: usize) -> String;
 }
 
 impl<'a> Generate for File<'a> {
@@ -19,40 +19,90 @@ impl<'a> Generate for RuleDef<'a> {
     }
 }
 
+impl<'a> Generate for CharClass<'a> {
+    fn generate(&self, _file: &File, _max_level: usize) -> String {
+        // pick a range by its size, then a character in it
+        let total: u32 = self
+            .ranges
+            .iter()
+            .map(|(lo, hi)| (*hi as u32 + 1).saturating_sub(*lo as u32))
+            .sum();
+        for _ in 0..100 {
+            let ch = if self.negated || total == 0 {
+                // printable ascii outside the class
+                std::char::from_u32(0x20 + (random::<u32>() % 0x5f))
+            } else {
+                let mut index = random::<u32>() % total;
+                self.ranges.iter().find_map(|(lo, hi)| {
+                    let size = (*hi as u32 + 1).saturating_sub(*lo as u32);
+                    if index < size {
+                        std::char::from_u32(*lo as u32 + index)
+                    } else {
+                        index -= size;
+                        None
+                    }
+                })
+            };
+            match ch {
+                Some(ch) if self.contains(ch) => return ch.to_string(),
+                _ => {}
+            }
+        }
+        // no luck, e.g. a negated class of all printable characters
+        self.overlap(self).map(String::from).unwrap_or_default()
+    }
+}
+
 impl<'a> Generate for Prod<'a> {
     fn generate(&self, file: &File, max_level: usize) -> String {
         use Prod::*;
         match self {
-            Concat(l, r) => {
+            Concat(l, r, _) => {
                 let mut ll = l.generate(file, max_level);
                 let rr = r.generate(file, max_level);
-                ll.push_str(" ");
+                ll.push(' ');
                 ll.push_str(&rr);
                 ll
             }
-            Terminal(s) => String::from(&s[1..s.len() - 1]),
-            NonTerminal(s) => {
+            Terminal(lit, _) => String::from(lit.value),
+            Class(class, _) => class.generate(file, max_level),
+            NonTerminal(s, _) => {
                 if let Some(term) = file.mapping.borrow().get(s) {
                     term.generate(file, max_level)
                 } else {
-                    panic!("{:?} not found", s)
+                    // left out because of a syntax error
+                    format!("<{}>", s)
                 }
             }
-            Optional(p) => {
+            Optional(p, _) => {
                 if random::<f64>() < 0.6 && max_level > 0 {
                     p.generate(file, max_level - 1)
                 } else {
                     String::new()
                 }
             }
-            Star(p) => {
+            Star(p, _) => {
                 let mut result = String::new();
                 while random::<f64>() < 0.6 && max_level > 0 {
                     result.push_str(&p.generate(file, max_level - 1));
                 }
                 result
             }
-            Eps => String::new(),
+            Repeat(p, min, max, _) => {
+                let mut count = *min;
+                while max.is_none_or(|max| count < max) && random::<f64>() < 0.6 && max_level > 0 {
+                    count += 1;
+                }
+                let words: Vec<String> = (0..count)
+                    .map(|_| p.generate(file, max_level.saturating_sub(1)))
+                    .collect();
+                words.join(" ")
+            }
+            Alt(alts, _) => {
+                let alt = alts[(alts.len() as f64 * random::<f64>()) as usize];
+                alt.generate(file, max_level)
+            }
+            Eps(_) => String::new(),
         }
     }
 }
@@ -0,0 +1,288 @@
+// front-end for ISO/IEC 14977 ebnf
+use crate::front::*;
+use crate::*;
+
+struct IsoParser<'a> {
+    s: Scanner<'a>,
+    alloc: &'a ASTAlloc<'a>,
+    errors: Errors,
+    // names of rules left out because of syntax errors after their name
+    broken: Vec<&'a str>,
+    // rules of the previous pass, to resolve exceptions
+    defs: Defs<'a>,
+    has_exception: bool,
+}
+
+// spaces inside meta identifiers are insignificant, so they become _
+fn is_id_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == ' ' || ch == '\t'
+}
+
+impl<'a> IsoParser<'a> {
+    // skip spaces and (nested) comments
+    fn gap(&mut self) {
+        loop {
+            self.s.take_while(char::is_whitespace);
+            if !self.s.rest().starts_with("(*") {
+                break;
+            }
+            let lo = self.s.pos;
+            self.s.pos += 2;
+            let mut depth = 1;
+            while depth > 0 {
+                if self.s.eat("(*") {
+                    depth += 1;
+                } else if self.s.eat("*)") {
+                    depth -= 1;
+                } else if self.s.bump().is_none() {
+                    return self
+                        .errors
+                        .issue(self.s.loc(lo), ErrorKind::UnterminatedComment);
+                }
+            }
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Option<()> {
+        self.gap();
+        if self.s.eat(s) {
+            Some(())
+        } else {
+            self.s.error(&mut self.errors, &[&format!("`{}`", s)]);
+            None
+        }
+    }
+
+    fn meta_id(&mut self) -> Option<&'a str> {
+        self.gap();
+        if !self.s.peek().is_some_and(|ch| ch.is_ascii_alphabetic()) {
+            self.s.error(&mut self.errors, &["meta identifier"]);
+            return None;
+        }
+        let raw = self.s.take_while(is_id_char);
+        let id = raw.trim_end();
+        // leave trailing spaces to gap
+        self.s.pos -= raw.len() - id.len();
+        if id.contains(char::is_whitespace) {
+            let words: Vec<&str> = id.split_whitespace().collect();
+            Some(self.alloc.string.alloc(words.join("_")))
+        } else {
+            Some(id)
+        }
+    }
+
+    fn rule(&mut self) -> Option<RuleDef<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        let name = self.meta_id()?;
+        // popped once the rule parses
+        self.broken.push(name);
+        self.expect("=")?;
+        let prod = self.definitions()?;
+        self.gap();
+        if !(self.s.eat(";") || self.s.eat(".")) {
+            self.s
+                .error(&mut self.errors, &["`;`", "`.`", "`|`", "`,`"]);
+            return None;
+        }
+        self.broken.pop();
+        Some(RuleDef {
+            name,
+            span: self.s.span(lo, self.s.pos),
+            prod,
+        })
+    }
+
+    // alternatives separated by | / or !
+    fn definitions(&mut self) -> Option<Vec<&'a Prod<'a>>> {
+        let mut res = vec![self.single_definition()?];
+        loop {
+            self.gap();
+            let rest = self.s.rest();
+            if rest.starts_with('|')
+                || rest.starts_with('!')
+                || (rest.starts_with('/') && !rest.starts_with("/)"))
+            {
+                self.s.bump();
+                res.push(self.single_definition()?);
+            } else {
+                return Some(res);
+            }
+        }
+    }
+
+    fn single_definition(&mut self) -> Option<&'a Prod<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        let mut terms = vec![self.term()?];
+        loop {
+            self.gap();
+            if !self.s.eat(",") {
+                break;
+            }
+            terms.push(self.term()?);
+        }
+        let span = self.s.span(lo, self.s.pos);
+        Some(make_concat(self.alloc, terms, span))
+    }
+
+    // factor with an optional exception
+    fn term(&mut self) -> Option<&'a Prod<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        let factor = self.factor()?;
+        self.gap();
+        if !self.s.eat("-") {
+            return Some(factor);
+        }
+        let except = self.factor()?;
+        self.has_exception = true;
+        let span = self.s.span(lo, self.s.pos);
+        let raw = self.s.code[lo..self.s.pos].trim_end();
+        Some(make_exception(
+            self.alloc,
+            &mut self.errors,
+            &self.defs,
+            (factor, except),
+            raw,
+            span,
+        ))
+    }
+
+    // primary with an optional repetition count
+    fn factor(&mut self) -> Option<&'a Prod<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        let count = match self.s.count(&mut self.errors)? {
+            Some(count) => count,
+            None => return self.primary(),
+        };
+        self.expect("*")?;
+        let primary = self.primary()?;
+        let span = self.s.span(lo, self.s.pos);
+        Some(make_repeat(self.alloc, primary, count, Some(count), span))
+    }
+
+    fn primary(&mut self) -> Option<&'a Prod<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        // (/ /) and (: :) are alternative representations of [ ] and { }
+        let brackets = [
+            ("(/", "/)"),
+            ("(:", ":)"),
+            ("[", "]"),
+            ("{", "}"),
+            ("(", ")"),
+        ];
+        for (open, close) in brackets.iter() {
+            if !self.s.eat(open) {
+                continue;
+            }
+            let alts = self.definitions()?;
+            self.expect(close)?;
+            let span = self.s.span(lo, self.s.pos);
+            let inner = make_alt(self.alloc, alts, span);
+            return Some(match *close {
+                "/)" | "]" => self.alloc.prod.alloc(Prod::Optional(inner, span)),
+                ":)" | "}" => self.alloc.prod.alloc(Prod::Star(inner, span)),
+                _ => inner,
+            });
+        }
+        match self.s.peek() {
+            Some(quote @ '\'') | Some(quote @ '"') | Some(quote @ '?') => {
+                self.s.bump();
+                self.s.take_while(|ch| ch != quote);
+                if self.s.bump().is_none() {
+                    return self
+                        .errors
+                        .issue(self.s.loc(lo), ErrorKind::UnrecognizedChar(quote));
+                }
+                let raw = &self.s.code[lo..self.s.pos];
+                // special sequences stand for themselves
+                let value = if quote == '?' {
+                    raw[1..raw.len() - 1].trim()
+                } else {
+                    &raw[1..raw.len() - 1]
+                };
+                let span = self.s.span(lo, self.s.pos);
+                Some(
+                    self.alloc
+                        .prod
+                        .alloc(Prod::Terminal(Literal { raw, value }, span)),
+                )
+            }
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                let name = self.meta_id()?;
+                let span = self.s.span(lo, self.s.pos);
+                Some(self.alloc.prod.alloc(Prod::NonTerminal(name, span)))
+            }
+            // empty sequence
+            None | Some(',') | Some('|') | Some('/') | Some('!') | Some(';') | Some('.')
+            | Some(')') | Some(']') | Some('}') | Some('-') | Some(':') => {
+                Some(self.alloc.prod.alloc(Prod::Eps(self.s.span(lo, lo))))
+            }
+            _ => {
+                self.s.error(
+                    &mut self.errors,
+                    &["meta identifier", "terminal", "`(`", "`[`", "`{`", "`?`"],
+                );
+                None
+            }
+        }
+    }
+
+    // skip past the terminator of a malformed rule
+    fn recover(&mut self) {
+        loop {
+            self.gap();
+            match self.s.peek() {
+                None => return,
+                Some(';') | Some('.') => {
+                    self.s.bump();
+                    return;
+                }
+                Some(quote @ '\'') | Some(quote @ '"') | Some(quote @ '?') => {
+                    self.s.bump();
+                    self.s.take_while(|ch| ch != quote);
+                    self.s.bump();
+                }
+                _ => {
+                    self.s.bump();
+                }
+            }
+        }
+    }
+
+    fn file(&mut self) -> Vec<RuleDef<'a>> {
+        let mut rules = vec![];
+        loop {
+            self.gap();
+            if self.s.peek().is_none() {
+                break;
+            }
+            match self.rule() {
+                Some(rule) => rules.push(rule),
+                None => self.recover(),
+            }
+        }
+        if rules.is_empty() && self.errors.0.is_empty() {
+            self.s.error(&mut self.errors, &["meta identifier"]);
+        }
+        rules
+    }
+}
+
+pub fn parse_iso<'a>(code: &'a str, alloc: &'a ASTAlloc<'a>) -> (&'a File<'a>, Errors) {
+    resolve_exceptions(alloc, |defs| {
+        let mut parser = IsoParser {
+            s: Scanner::new(code),
+            alloc,
+            errors: Errors::default(),
+            broken: vec![],
+            defs,
+            has_exception: false,
+        };
+        let rules = parser.file();
+        (rules, parser.broken, parser.errors, parser.has_exception)
+    })
+}
@@ -0,0 +1,227 @@
+// emitter of lalrpop grammars, nonterminals have type () and actions do nothing
+use crate::emit::*;
+use crate::*;
+use std::collections::BTreeMap;
+
+const KEYWORDS: &[&str] = &["grammar"];
+
+// raw string with enough # for its content
+fn raw_string(value: &str) -> String {
+    let mut hashes = String::new();
+    while value.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+    format!("r{}\"{}\"{}", hashes, value, hashes)
+}
+
+struct Lalrpop<'a> {
+    names: Names<'a>,
+    // nonterminals generated for alternatives inside productions
+    helpers: Vec<(String, Vec<&'a Prod<'a>>)>,
+    // nonterminals for classes matching literals, with their regex and the literals
+    class_rules: Vec<(String, String, Vec<&'a str>)>,
+    values: BTreeMap<String, &'a str>,
+}
+
+impl<'a> Lalrpop<'a> {
+    fn rule_name(&mut self, name: &'a str) -> String {
+        self.names.get(name, || camel_case(name))
+    }
+
+    // the lexer prefers literals to regexes, so a class matching some literal
+    // is a nonterminal that also takes the literal
+    fn class(&mut self, class: &'a CharClass<'a>) -> String {
+        let regex = raw_string(&regex_class(class));
+        if let Some((name, ..)) = self.class_rules.iter().find(|(_, r, _)| *r == regex) {
+            return name.clone();
+        }
+        let literals = class_literals(&self.values, class);
+        if literals.is_empty() {
+            return regex;
+        }
+        let name = self.names.fresh(format!("Class{}", self.class_rules.len()));
+        self.class_rules.push((name.clone(), regex, literals));
+        name
+    }
+
+    // operand of a postfix operator
+    fn atom(&mut self, owner: &str, prod: &'a Prod<'a>) -> String {
+        let res = self.seq(owner, prod);
+        match prod {
+            Prod::Terminal(..) | Prod::Class(..) | Prod::NonTerminal(..) | Prod::Alt(..) => res,
+            _ => format!("({})", res),
+        }
+    }
+
+    // owner is the nonterminal being written, which names the helpers
+    fn seq(&mut self, owner: &str, prod: &'a Prod<'a>) -> String {
+        match prod {
+            Prod::Concat(l, r, _) => match as_plus(prod) {
+                Some(p) => format!("{}+", self.atom(owner, p)),
+                None => {
+                    let (l, r) = (self.seq(owner, l), self.seq(owner, r));
+                    match (l.is_empty(), r.is_empty()) {
+                        (true, _) => r,
+                        (_, true) => l,
+                        _ => format!("{} {}", l, r),
+                    }
+                }
+            },
+            // the lexer never produces an empty token
+            Prod::Terminal(lit, _) if lit.value.is_empty() => String::new(),
+            Prod::Terminal(lit, _) => format!("{:?}", lit.value),
+            Prod::Class(class, _) => self.class(class),
+            Prod::NonTerminal(name, _) => self.rule_name(name),
+            Prod::Optional(p, _) => format!("{}?", self.atom(owner, p)),
+            Prod::Star(p, _) => format!("{}*", self.atom(owner, p)),
+            // there is no counted repetition, so copies are written out
+            Prod::Repeat(p, min, max, _) => {
+                let p = self.atom(owner, p);
+                let mut res = vec![p.clone(); *min];
+                match max {
+                    None if *min > 0 => res[*min - 1].push('+'),
+                    None => res.push(format!("{}*", p)),
+                    Some(max) if max > min => {
+                        let mut tail = format!("{}?", p);
+                        for _ in min + 1..*max {
+                            tail = format!("({} {})?", p, tail);
+                        }
+                        res.push(tail);
+                    }
+                    Some(_) => {}
+                }
+                res.join(" ")
+            }
+            // parentheses only group a sequence, alternatives need a nonterminal
+            Prod::Alt(alts, _) => {
+                let name = self.names.fresh(format!("{}Group", owner));
+                self.helpers.push((name.clone(), alts.clone()));
+                name
+            }
+            Prod::Eps(_) => String::new(),
+        }
+    }
+
+    fn rule(&mut self, res: &mut String, name: &str, public: bool, alts: Vec<&'a Prod<'a>>) {
+        use std::fmt::Write;
+        let vis = if public { "pub " } else { "" };
+        let _ = writeln!(res, "\n{}{}: () = {{", vis, name);
+        for alt in alts {
+            let body = self.seq(name, alt);
+            let sep = if body.is_empty() { "" } else { " " };
+            let _ = writeln!(res, "    {}{}=> (),", body, sep);
+        }
+        res.push_str("};\n");
+    }
+}
+
+// the first rule is the public start symbol
+pub fn emit_lalrpop(file: &File) -> String {
+    let mut lalrpop = Lalrpop {
+        names: Names::new(&[KEYWORDS, RUST_KEYWORDS].concat()),
+        helpers: vec![],
+        class_rules: vec![],
+        values: literal_values(file),
+    };
+    let mut res = String::from("grammar;\n");
+    if !file.precs.is_empty() {
+        res.push_str("\n// precedence from lowest to highest\n");
+        res.push_str(
+            "// lalrpop has no declarations for it, the grammar has to be layered instead\n",
+        );
+        for prec in file.precs.iter() {
+            res.push_str(&format!("// {:?} {}\n", prec.assoc, prec.terms.join(" ")));
+        }
+    }
+    for (i, (name, alts)) in grouped_rules(file).into_iter().enumerate() {
+        let name = lalrpop.rule_name(name);
+        lalrpop.rule(&mut res, &name, i == 0, alts);
+        // helpers may generate helpers of their own
+        while !lalrpop.helpers.is_empty() {
+            let (helper, alts) = lalrpop.helpers.remove(0);
+            lalrpop.rule(&mut res, &helper, false, alts);
+        }
+    }
+    for (name, regex, literals) in lalrpop.class_rules.iter() {
+        res.push_str(&format!("\n{}: () = {{\n    {} => (),\n", name, regex));
+        for value in literals.iter() {
+            res.push_str(&format!("    {:?} => (),\n", value));
+        }
+        res.push_str("};\n");
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(code: &str) -> String {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        emit_lalrpop(file)
+    }
+
+    #[test]
+    fn lalrpop_classes_take_their_literals() {
+        let code = "S ::= Num ('+' Num)* | Id;\nNum ::= Digit+ '.' Digit+;\nDigit ::= [0-9];\n\
+                    Id ::= [a-z] [a-z0-9]* | 'if' 'x'?;\n";
+        let expected = r#"grammar;
+
+pub S: () = {
+    Num ("+" Num)* => (),
+    Id => (),
+};
+
+Num: () = {
+    Digit+ "." Digit+ => (),
+};
+
+Digit: () = {
+    r"[0-9]" => (),
+};
+
+Id: () = {
+    Class0 Class1* => (),
+    "if" "x"? => (),
+};
+
+Class0: () = {
+    r"[a-z]" => (),
+    "x" => (),
+};
+
+Class1: () = {
+    r"[a-z0-9]" => (),
+    "x" => (),
+};
+"#;
+        assert_eq!(emitted(code), expected);
+    }
+
+    #[test]
+    fn lalrpop_alternatives_become_nonterminals() {
+        let expected = r#"grammar;
+
+pub S: () = {
+    Class0 SGroup SGroup? "a" r"[^a-z]" => (),
+};
+
+SGroup: () = {
+    Class0 => (),
+    "x" => (),
+};
+
+Class0: () = {
+    r"[a-z]" => (),
+    "a" => (),
+    "x" => (),
+};
+"#;
+        assert_eq!(
+            emitted("S ::= [a-z] ([a-z] | 'x'){1,2} 'a' [^a-z];"),
+            expected
+        );
+    }
+}
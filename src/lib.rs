@@ -1,57 +1,426 @@
 #![feature(proc_macro_hygiene)] // allow proc macro output macro definition
 
+mod abnf;
+mod antlr;
 mod ast;
+mod bison;
+mod check;
+mod emit;
 mod errors;
 mod flatten;
+mod format;
+mod front;
 mod gen;
+mod iso;
+mod lalrpop;
 mod ll;
 mod loc;
 mod lr;
+mod pest;
+mod skeleton;
+mod trace;
+mod tree;
+mod w3c;
 
+pub use abnf::*;
+pub use antlr::*;
 pub use ast::*;
+pub use bison::*;
+pub use check::*;
+pub use emit::*;
 pub use errors::*;
 pub use flatten::*;
+pub use format::*;
 pub use gen::*;
+pub use iso::*;
+pub use lalrpop::*;
 pub use ll::*;
 pub use loc::*;
 pub use lr::*;
+pub use pest::*;
+pub use skeleton::*;
+pub use trace::*;
+pub use tree::*;
+pub use w3c::*;
 
 use parser_macros::lalr1;
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 
-pub fn work<'p>(code: &'p str, alloc: &'p ASTAlloc<'p>) -> Result<&'p File<'p>, Errors> {
+// on syntax errors, the returned file only contains the rules that parsed
+pub fn work<'p>(code: &'p str, alloc: &'p ASTAlloc<'p>) -> (&'p File<'p>, Errors) {
     let mut parser = Parser {
         alloc,
-        error: Errors::default(),
+        code,
+        lines: Lines::new(code),
+        error: RefCell::new(Errors::default()),
     };
     let mut lexer = Lexer::new(code.as_bytes()); // Lexer can be used independently from Parser, you can use it to debug
-    match parser.parse(&mut lexer) {
-        Ok(program) if parser.error.0.is_empty() => {
-            let mut mapping = program.mapping.borrow_mut();
-            for rule in program.rules.iter() {
-                mapping.insert(rule.name, rule);
+    let program = match parser.parse(&mut lexer) {
+        Ok(program) => {
+            if program.rules.is_empty() {
+                // an empty grammar is reported as a missing rule at the end
+                let mut lexer = Lexer::new(code.as_bytes());
+                let eof = loop {
+                    let token = lexer.next();
+                    if let TokenKind::_Eof = token.ty {
+                        break token;
+                    }
+                };
+                with_token_table(|table| parser.syntax_error(table, 0, code, &eof));
             }
-            Ok(program)
+            program
         }
         Err(token) => {
-            let mut error = parser.error;
-            let loc = Loc(token.line, token.col);
-            match token.ty {
-                TokenKind::_Err => {
-                    error.issue(loc, ErrorKind::UnrecognizedChar(token.piece[0] as char))
-                }
-                _ => error.issue(loc, ErrorKind::SyntaxError),
-            }
-            Err(error)
+            // each statement is parsed again on its own, issuing the errors of its actions again
+            parser.error.replace(Errors::default());
+            with_token_table(|table| parser.recover(table, &token))
+        }
+    };
+    let mut mapping = program.mapping.borrow_mut();
+    for rule in program.rules.iter() {
+        mapping.insert(rule.name, rule);
+    }
+    (program, parser.error.into_inner().sorted())
+}
+
+// next character of a terminal or class body, and whether it is escaped
+// the lexer only accepts valid escapes, others come from input sentences and are taken as they are
+fn next_char(chars: &mut std::str::Chars) -> Option<(char, bool)> {
+    let ch = chars.next()?;
+    if ch != '\\' {
+        return Some((ch, false));
+    }
+    let ch = match chars.next() {
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('t') => '\t',
+        Some('u') => {
+            let hex: String = chars.by_ref().skip(1).take_while(|ch| *ch != '}').collect();
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .unwrap_or(std::char::REPLACEMENT_CHARACTER)
+        }
+        Some(ch) => ch,
+        None => return Some(('\\', false)),
+    };
+    Some((ch, true))
+}
+
+// the grammar of the parser below over token kinds
+const TOKEN_GRAMMAR: &str = "
+File ::= PrecList RuleList;
+PrecList ::= PrecList Prec | _;
+Prec ::= 'Left' TermList 'Comma' | 'Right' TermList 'Comma' | 'NonAssoc' TermList 'Comma';
+TermList ::= TermList 'StringLit' | 'StringLit';
+RuleList ::= Rule RuleList | _;
+Rule ::= 'Id' 'Def' ProdList 'Comma';
+ProdList ::= Prod ProdListRem;
+ProdListRem ::= 'Or' Prod ProdListRem | _;
+Prod1 ::= 'LPar' ProdList 'RPar' | Prod1 'Plus' | Prod1 'Opt' | Prod1 'Repeat' | Prod1 'Star'
+    | 'Eps' | 'Id' | 'StringLit' | 'Class';
+Prod ::= Prod Prod1 | Prod1;
+";
+
+// run f with the lr(1) table of TOKEN_GRAMMAR, built once for all the errors of a file
+// unlike lalr(1) states, the states of the table expect no token from another context
+fn with_token_table<T>(f: impl FnOnce(&LrTable) -> T) -> T {
+    let alloc = ASTAlloc::default();
+    let (file, _) = work(TOKEN_GRAMMAR, &alloc);
+    let flatten_alloc = FlattenAlloc::default();
+    let rules = flatten(file, &flatten_alloc);
+    let graph = lr1_graph(&rules);
+    f(&lr1_table(&graph, &file.precs))
+}
+
+// tokens the parser accepts after prefix, read from the table at the state prefix leads to
+fn expected_tokens(table: &LrTable, prefix: &str) -> Vec<TokenKind> {
+    use TokenKind::*;
+    let kinds = [
+        LPar, Eps, Id, StringLit, Class, Star, Plus, Opt, Repeat, Or, RPar, Def, Comma, Left,
+        Right, NonAssoc, _Eof,
+    ];
+    let mut input = vec![];
+    let mut lexer = Lexer::new(prefix.as_bytes());
+    loop {
+        match lexer.next().ty {
+            _Eof => break,
+            ty => input.push(ty.terminal()),
+        }
+    }
+    let expected = lr_expected(table, &input);
+    kinds
+        .iter()
+        .filter(|ty| expected.contains(&ty.terminal()))
+        .cloned()
+        .collect()
+}
+
+// name of the rule a statement defines, if it gets as far as `::=`
+fn head(chunk: &str) -> Option<&str> {
+    let mut lexer = Lexer::new(chunk.as_bytes());
+    let name = lexer.next();
+    match (name.ty, lexer.next().ty) {
+        (TokenKind::Id, TokenKind::Def) => Some(name.str()),
+        _ => None,
+    }
+}
+
+impl TokenKind {
+    // name of the token in TOKEN_GRAMMAR
+    fn terminal(self) -> &'static str {
+        use TokenKind::*;
+        match self {
+            Star => "'Star'",
+            Plus => "'Plus'",
+            Opt => "'Opt'",
+            Repeat => "'Repeat'",
+            LPar => "'LPar'",
+            RPar => "'RPar'",
+            Or => "'Or'",
+            Comma => "'Comma'",
+            Eps => "'Eps'",
+            Def => "'Def'",
+            Left => "'Left'",
+            Right => "'Right'",
+            NonAssoc => "'NonAssoc'",
+            StringLit => "'StringLit'",
+            Class => "'Class'",
+            Id => "'Id'",
+            _Eof => "#",
+            _ => "'_Err'",
+        }
+    }
+
+    // how the token is called in diagnostics
+    pub fn describe(self) -> &'static str {
+        use TokenKind::*;
+        match self {
+            Star => "`*`",
+            Plus => "`+`",
+            Opt => "`?`",
+            Repeat => "`{n,m}`",
+            LPar => "`(`",
+            RPar => "`)`",
+            Or => "`|`",
+            Comma => "`;`",
+            Eps => "`_`",
+            Def => "`::=`",
+            Left => "`%left`",
+            Right => "`%right`",
+            NonAssoc => "`%nonassoc`",
+            StringLit => "quoted terminal",
+            Class => "character class",
+            Id => "identifier",
+            _Eof => "end of file",
+            _ => "unknown token",
         }
-        _ => Err(parser.error),
     }
 }
 
 pub struct Parser<'p> {
     pub alloc: &'p ASTAlloc<'p>,
-    pub error: Errors,
+    pub code: &'p str,
+    pub lines: Lines,
+    // rule actions only borrow the parser
+    pub error: RefCell<Errors>,
+}
+
+impl<'p> Parser<'p> {
+    pub fn offset(&self, token: &Token<'p>) -> usize {
+        token.piece.as_ptr() as usize - self.code.as_ptr() as usize
+    }
+
+    pub fn loc_of(&self, offset: usize) -> Loc {
+        self.lines.loc(self.code, offset)
+    }
+
+    // locations come from byte offsets, because the lexer may start in the middle of code
+    pub fn span(&self, token: &Token<'p>) -> Span {
+        let lo = self.offset(token);
+        let hi = lo + token.piece.len();
+        Span {
+            start: self.loc_of(lo),
+            end: self.loc_of(hi),
+            lo,
+            hi,
+        }
+    }
+
+    // quoted terminal, with escapes resolved in its value
+    pub fn literal(&self, token: &Token<'p>) -> Literal<'p> {
+        let raw = token.str();
+        let inner = &raw[1..raw.len() - 1];
+        if !inner.contains('\\') {
+            return Literal { raw, value: inner };
+        }
+        let mut value = String::new();
+        let mut chars = inner.chars();
+        while let Some((ch, _)) = next_char(&mut chars) {
+            value.push(ch);
+        }
+        Literal {
+            raw,
+            value: self.alloc.string.alloc(value),
+        }
+    }
+
+    // name of a quoted terminal, like flattened rules name it
+    pub fn term(&self, token: &Token<'p>) -> &'p str {
+        let raw = token.str();
+        let name = terminal_name(self.literal(token).value);
+        if name == raw {
+            raw
+        } else {
+            self.alloc.string.alloc(name)
+        }
+    }
+
+    pub fn class(&self, token: &Token<'p>) -> CharClass<'p> {
+        let raw = token.str();
+        if !raw.starts_with('[') {
+            // 'a'..'z', the lexer only accepts single characters as bounds
+            let mut chars = raw[1..].chars();
+            let (lo, _) = next_char(&mut chars).unwrap();
+            // skip the quotes and dots in between
+            chars.nth(3);
+            let (hi, _) = next_char(&mut chars).unwrap();
+            return CharClass {
+                raw,
+                negated: false,
+                ranges: vec![(lo, hi)],
+            };
+        }
+        let negated = raw.starts_with("[^");
+        let inner = &raw[if negated { 2 } else { 1 }..raw.len() - 1];
+        let mut chars = inner.chars();
+        let mut ranges = vec![];
+        while let Some((lo, _)) = next_char(&mut chars) {
+            // a-b is a range, while a leading or trailing - is itself
+            let mut ahead = chars.clone();
+            match (next_char(&mut ahead), next_char(&mut ahead)) {
+                (Some(('-', false)), Some((hi, _))) => {
+                    ranges.push((lo, hi));
+                    chars = ahead;
+                }
+                _ => ranges.push((lo, lo)),
+            }
+        }
+        CharClass {
+            raw,
+            negated,
+            ranges,
+        }
+    }
+
+    // report token as unexpected in chunk, which starts at byte offset lo of code
+    // returns byte offset of the token
+    fn syntax_error(
+        &mut self,
+        table: &LrTable,
+        lo: usize,
+        chunk: &'p str,
+        token: &Token<'p>,
+    ) -> usize {
+        let offset = match token.ty {
+            TokenKind::_Eof => lo + chunk.len(),
+            _ => self.offset(token),
+        };
+        let loc = self.loc_of(offset);
+        match token.ty {
+            TokenKind::_Err => self.error.borrow_mut().issue(
+                loc,
+                ErrorKind::UnrecognizedChar(self.code[offset..].chars().next().unwrap()),
+            ),
+            _ => {
+                let expected = expected_tokens(table, &self.code[lo..offset])
+                    .into_iter()
+                    .map(|ty| ty.describe().to_string())
+                    .collect();
+                let found = match token.ty {
+                    // a chunk may end before the next statement
+                    TokenKind::_Eof if offset < self.code.len() => {
+                        let mut lexer = Lexer::new(&self.code.as_bytes()[offset..]);
+                        format!("`{}`", lexer.next().str())
+                    }
+                    TokenKind::_Eof => TokenKind::_Eof.describe().to_string(),
+                    _ => format!("`{}`", token.str()),
+                };
+                self.error
+                    .borrow_mut()
+                    .issue(loc, ErrorKind::SyntaxError(found, expected))
+            }
+        }
+        offset
+    }
+
+    // parse each statement on its own, collecting rules and errors
+    // a statement ends with `;`, or where `Id ::=` starts the next one if `;` is missing
+    // token is where parsing the whole code failed, the statement it is in is skipped
+    fn recover(&mut self, table: &LrTable, token: &Token<'p>) -> &'p File<'p> {
+        let code = self.code;
+        let mut bounds = vec![0];
+        // offsets of `::=` of statements not following `;`
+        let mut heads = vec![];
+        let mut prev_id = None;
+        let mut lexer = Lexer::new(code.as_bytes());
+        loop {
+            let token = lexer.next();
+            match token.ty {
+                TokenKind::_Eof => break,
+                TokenKind::Comma => bounds.push(self.offset(&token) + 1),
+                TokenKind::Def => match prev_id {
+                    Some(id) if id > *bounds.last().unwrap() => {
+                        bounds.push(id);
+                        heads.push(self.offset(&token));
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            prev_id = match token.ty {
+                TokenKind::Id => Some(self.offset(&token)),
+                _ => None,
+            };
+        }
+        if bounds.last() != Some(&code.len()) {
+            bounds.push(code.len());
+        }
+
+        // `::=` after a statement missing `;` is where the whole code fails
+        // the error is then found again in that statement
+        let first = match token.ty {
+            TokenKind::Def if heads.contains(&self.offset(token)) => None,
+            _ => Some(self.syntax_error(table, 0, code, token)),
+        };
+        let (mut rules, mut precs, mut broken) = (vec![], vec![], vec![]);
+        for bound in bounds.windows(2) {
+            let (lo, hi) = (bound[0], bound[1]);
+            let chunk = &code[lo..hi];
+            if first.is_some_and(|first| lo <= first && (first < hi || hi == code.len())) {
+                broken.extend(head(chunk));
+                continue;
+            }
+            let mut lexer = Lexer::new(chunk.as_bytes());
+            match self.parse(&mut lexer) {
+                Ok(file) => {
+                    rules.extend(file.rules.iter().cloned());
+                    precs.extend(file.precs.iter().cloned());
+                }
+                Err(token) => {
+                    self.syntax_error(table, lo, chunk, &token);
+                    broken.extend(head(chunk));
+                }
+            }
+        }
+        self.alloc.file.alloc(File {
+            rules,
+            precs,
+            broken,
+            mapping: RefCell::new(BTreeMap::new()),
+        })
+    }
 }
 
 impl<'p> Token<'p> {
@@ -80,13 +449,21 @@ priority = [
 '\*' = 'Star'
 '\+' = 'Plus'
 '\?' = 'Opt'
+'\{[0-9]+(,[0-9]*)?\}' = 'Repeat'
 '\(' = 'LPar' # short for parenthesis
 '\)' = 'RPar'
 '\|' = 'Or'
 ';' = 'Comma'
 '_' = 'Eps'
 '::=' = 'Def'
-"'[^']+'" = 'StringLit'
+'%left' = 'Left'
+'%right' = 'Right'
+'%nonassoc' = 'NonAssoc'
+"'([^'\\\\]|\\\\(['\"\\\\nrt]|u\\{[0-9a-fA-F]{1,6}\\}))*'" = 'StringLit'
+"\"([^\"\\\\]|\\\\(['\"\\\\nrt]|u\\{[0-9a-fA-F]{1,6}\\}))*\"" = 'StringLit'
+"\\[\\^?([^\\]\\\\]|\\\\([\\]\\\\\\-^nrt]|u\\{[0-9a-fA-F]{1,6}\\}))*\\]" = 'Class'
+"'([^'\\\\]|\\\\(['\"\\\\nrt]|u\\{[0-9a-fA-F]{1,6}\\}))'\\.\\.'([^'\\\\]|\\\\(['\"\\\\nrt]|u\\{[0-9a-fA-F]{1,6}\\}))'" = 'Class'
+"\"([^\"\\\\]|\\\\(['\"\\\\nrt]|u\\{[0-9a-fA-F]{1,6}\\}))\"\\.\\.\"([^\"\\\\]|\\\\(['\"\\\\nrt]|u\\{[0-9a-fA-F]{1,6}\\}))\"" = 'Class'
 '//[^\n]*' = '_Eps'
 '\s+' = '_Eps'
 '[A-Za-z]\w*' = 'Id'
@@ -94,23 +471,70 @@ priority = [
 "##
 )]
 impl<'p> Parser<'p> {
-    #[rule(File -> RuleList)]
-    fn file(&self, mut l: Vec<RuleDef<'p>>) -> &'p File<'p> {
+    #[rule(File -> PrecList RuleList)]
+    fn file(&self, p: Vec<PrecDef<'p>>, mut l: Vec<RuleDef<'p>>) -> &'p File<'p> {
         l.reverse();
         self.alloc.file.alloc(File {
             rules: l,
+            precs: p,
+            broken: vec![],
             mapping: RefCell::new(BTreeMap::new()),
         })
     }
 
+    #[rule(PrecList -> PrecList Prec)]
+    fn prec_list(&self, mut l: Vec<PrecDef<'p>>, p: PrecDef<'p>) -> Vec<PrecDef<'p>> {
+        l.push(p);
+        l
+    }
+    #[rule(PrecList -> )]
+    fn prec_list_0(&self) -> Vec<PrecDef<'p>> {
+        vec![]
+    }
+
+    #[rule(Prec -> Left TermList Comma)]
+    fn prec_left(&self, a: Token<'p>, terms: Vec<&'p str>, _c: Token<'p>) -> PrecDef<'p> {
+        PrecDef {
+            assoc: Assoc::Left,
+            terms,
+            loc: self.span(&a).start,
+        }
+    }
+    #[rule(Prec -> Right TermList Comma)]
+    fn prec_right(&self, a: Token<'p>, terms: Vec<&'p str>, _c: Token<'p>) -> PrecDef<'p> {
+        PrecDef {
+            assoc: Assoc::Right,
+            terms,
+            loc: self.span(&a).start,
+        }
+    }
+    #[rule(Prec -> NonAssoc TermList Comma)]
+    fn prec_nonassoc(&self, a: Token<'p>, terms: Vec<&'p str>, _c: Token<'p>) -> PrecDef<'p> {
+        PrecDef {
+            assoc: Assoc::NonAssoc,
+            terms,
+            loc: self.span(&a).start,
+        }
+    }
+
+    #[rule(TermList -> TermList StringLit)]
+    fn term_list(&self, mut l: Vec<&'p str>, lit: Token<'p>) -> Vec<&'p str> {
+        l.push(self.term(&lit));
+        l
+    }
+    #[rule(TermList -> StringLit)]
+    fn term_list1(&self, lit: Token<'p>) -> Vec<&'p str> {
+        vec![self.term(&lit)]
+    }
+
     #[rule(RuleList -> Rule RuleList)]
     fn rule_list(&self, r: RuleDef<'p>, mut l: Vec<RuleDef<'p>>) -> Vec<RuleDef<'p>> {
         l.push(r);
         l
     }
-    #[rule(RuleList -> Rule)]
-    fn rule_list1(&self, r: RuleDef<'p>) -> Vec<RuleDef<'p>> {
-        vec![r]
+    #[rule(RuleList -> )]
+    fn rule_list_0(&self) -> Vec<RuleDef<'p>> {
+        vec![]
     }
 
     #[rule(Rule -> Id Def ProdList Comma)]
@@ -119,10 +543,11 @@ impl<'p> Parser<'p> {
         id: Token<'p>,
         _d: Token<'p>,
         prod: Vec<&'p Prod<'p>>,
-        _c: Token,
+        c: Token<'p>,
     ) -> RuleDef<'p> {
         RuleDef {
             name: id.str(),
+            span: self.span(&id).to(self.span(&c)),
             prod,
         }
     }
@@ -148,30 +573,70 @@ impl<'p> Parser<'p> {
         vec![]
     }
 
-    #[rule(Prod1 -> LPar Prod RPar)]
-    fn rule_paren(&self, _l: Token<'p>, p: &'p Prod<'p>, _r: Token<'p>) -> &'p Prod<'p> {
-        p
+    #[rule(Prod1 -> LPar ProdList RPar)]
+    fn rule_paren(&self, l: Token<'p>, mut p: Vec<&'p Prod<'p>>, r: Token<'p>) -> &'p Prod<'p> {
+        if p.len() == 1 {
+            return p[0];
+        }
+        // ProdList comes in reverse order
+        p.reverse();
+        let span = self.span(&l).to(self.span(&r));
+        self.alloc.prod.alloc(Prod::Alt(p, span))
     }
 
     #[rule(Prod1 -> Prod1 Plus)]
-    fn rule_plus(&self, p: &'p Prod<'p>, _p: Token<'p>) -> &'p Prod<'p> {
-        let star = self.alloc.prod.alloc(Prod::Star(p));
-        self.alloc.prod.alloc(Prod::Concat(p, star))
+    fn rule_plus(&self, p: &'p Prod<'p>, plus: Token<'p>) -> &'p Prod<'p> {
+        let span = p.span().to(self.span(&plus));
+        let star = self.alloc.prod.alloc(Prod::Star(p, span));
+        self.alloc.prod.alloc(Prod::Concat(p, star, span))
     }
 
     #[rule(Prod1 -> Prod1 Opt)]
-    fn rule_opt(&self, p: &'p Prod<'p>, _o: Token<'p>) -> &'p Prod<'p> {
-        self.alloc.prod.alloc(Prod::Optional(p))
+    fn rule_opt(&self, p: &'p Prod<'p>, o: Token<'p>) -> &'p Prod<'p> {
+        let span = p.span().to(self.span(&o));
+        self.alloc.prod.alloc(Prod::Optional(p, span))
+    }
+
+    #[rule(Prod1 -> Prod1 Repeat)]
+    fn rule_repeat(&self, p: &'p Prod<'p>, r: Token<'p>) -> &'p Prod<'p> {
+        let span = p.span().to(self.span(&r));
+        // {n}, {n,} or {n,m}, the lexer only accepts digits but they may not fit in a usize
+        let raw = r.str();
+        let count = |bound: &str| match bound.parse::<usize>() {
+            Ok(count) => Some(count),
+            Err(_) => {
+                self.error
+                    .borrow_mut()
+                    .issue::<()>(span.start, ErrorKind::RepeatTooLarge(bound.to_string()));
+                None
+            }
+        };
+        let mut bounds = raw[1..raw.len() - 1].split(',');
+        let min = match count(bounds.next().unwrap_or("")) {
+            Some(min) => min,
+            None => return p,
+        };
+        let max = match bounds.next() {
+            None => Some(min),
+            Some("") => None,
+            Some(max) => match count(max) {
+                Some(max) => Some(max),
+                None => return p,
+            },
+        };
+        self.alloc.prod.alloc(Prod::Repeat(p, min, max, span))
     }
 
     #[rule(Prod1 -> Prod1 Star)]
-    fn rule_star(&self, p: &'p Prod<'p>, _s: Token<'p>) -> &'p Prod<'p> {
-        self.alloc.prod.alloc(Prod::Star(p))
+    fn rule_star(&self, p: &'p Prod<'p>, s: Token<'p>) -> &'p Prod<'p> {
+        let span = p.span().to(self.span(&s));
+        self.alloc.prod.alloc(Prod::Star(p, span))
     }
 
     #[rule(Prod -> Prod Prod1)]
     fn rule_concat(&self, l: &'p Prod<'p>, r: &'p Prod<'p>) -> &'p Prod<'p> {
-        self.alloc.prod.alloc(Prod::Concat(l, r))
+        let span = l.span().to(r.span());
+        self.alloc.prod.alloc(Prod::Concat(l, r, span))
     }
 
     #[rule(Prod -> Prod1)]
@@ -180,17 +645,147 @@ impl<'p> Parser<'p> {
     }
 
     #[rule(Prod1 -> Eps)]
-    fn prod_eps(&self, _e: Token<'p>) -> &'p Prod<'p> {
-        self.alloc.prod.alloc(Prod::Eps)
+    fn prod_eps(&self, e: Token<'p>) -> &'p Prod<'p> {
+        self.alloc.prod.alloc(Prod::Eps(self.span(&e)))
     }
 
     #[rule(Prod1 -> Id)]
     fn prod_id(&self, id: Token<'p>) -> &'p Prod<'p> {
-        self.alloc.prod.alloc(Prod::NonTerminal(id.str()))
+        self.alloc
+            .prod
+            .alloc(Prod::NonTerminal(id.str(), self.span(&id)))
     }
 
     #[rule(Prod1 -> StringLit)]
     fn prod_string(&self, lit: Token<'p>) -> &'p Prod<'p> {
-        self.alloc.prod.alloc(Prod::Terminal(lit.str()))
+        self.alloc
+            .prod
+            .alloc(Prod::Terminal(self.literal(&lit), self.span(&lit)))
+    }
+
+    #[rule(Prod1 -> Class)]
+    fn prod_class(&self, class: Token<'p>) -> &'p Prod<'p> {
+        self.alloc
+            .prod
+            .alloc(Prod::Class(self.class(&class), self.span(&class)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(code: &str) -> Vec<String> {
+        let alloc = ASTAlloc::default();
+        let (_, errors) = work(code, &alloc);
+        errors.0.iter().map(|e| format!("{:?}", e)).collect()
+    }
+
+    #[test]
+    fn token_grammar_has_no_conflicts() {
+        with_token_table(|table| assert!(table.conflicts().is_empty()));
+    }
+
+    #[test]
+    fn syntax_errors_list_expected_tokens() {
+        let found = messages("S ::= 'a';\nA ::= ;\nB ::= 'b' | ;\nC ::= 'c'");
+        let prod = "`(` `_` identifier quoted terminal character class";
+        assert_eq!(
+            found,
+            vec![
+                format!("*** Error at (2,7): expected one of {}, found `;`", prod),
+                format!("*** Error at (3,13): expected one of {}, found `;`", prod),
+                format!(
+                    "*** Error at (4,10): expected one of {} `*` `+` `?` `{{n,m}}` `|` `;`, found end of file",
+                    prod
+                ),
+            ]
+        );
+        // precedence is only declared before the rules
+        assert_eq!(
+            messages("S ::= 'a';\n%left 'a';\n"),
+            vec!["*** Error at (2,1): expected one of identifier end of file, found `%left`"]
+        );
+    }
+
+    #[test]
+    fn recover_reports_every_broken_rule() {
+        let alloc = ASTAlloc::default();
+        let code = "S ::= A B C;\nA ::= 'a'\nB ::= ;\nC ::= 'c';\n";
+        let (file, errors) = work(code, &alloc);
+        let prod = "`(` `_` identifier quoted terminal character class";
+        assert_eq!(
+            errors.0.iter().map(|e| format!("{:?}", e)).collect::<Vec<_>>(),
+            vec![
+                format!(
+                    "*** Error at (3,1): expected one of {} `*` `+` `?` `{{n,m}}` `|` `;`, found `B`",
+                    prod
+                ),
+                format!("*** Error at (3,7): expected one of {}, found `;`", prod),
+            ]
+        );
+        let names: Vec<&str> = file.rules.iter().map(|rule| rule.name).collect();
+        assert_eq!(names, vec!["S", "C"]);
+        assert_eq!(file.broken, vec!["A", "B"]);
+        assert!(check(file).0.is_empty());
+    }
+
+    #[test]
+    fn terminals_have_one_name() {
+        let alloc = ASTAlloc::default();
+        let code = "%left \"+\" '-' \"*\";\nS ::= E;\nE ::= E '+' E | E \"-\" E | E '*' E | 'n';\n";
+        let (file, errors) = work(code, &alloc);
+        assert!(errors.0.is_empty());
+        assert_eq!(file.precs[0].terms, vec!["'+'", "'-'", "'*'"]);
+        assert!(check(file).0.is_empty());
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        let graph = lr_graph(&rules);
+        assert!(lalr1_table(&graph, &file.precs).conflicts().is_empty());
+    }
+
+    #[test]
+    fn escapes_are_resolved() {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work("S ::= 'it\\'s' \"\\u{41}\\n\" '\"' '';", &alloc);
+        assert!(errors.0.is_empty());
+        let mut values = vec![];
+        visit(file.rules[0].prod[0], &mut |prod| {
+            if let Prod::Terminal(lit, _) = prod {
+                values.push(lit.value);
+            }
+        });
+        assert_eq!(values, vec!["it's", "A\n", "\"", ""]);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        assert_eq!(rules[0].to_string(), "S ::= 'it\\'s' 'A\\n' '\"' ''");
+    }
+
+    #[test]
+    fn classes_are_parsed() {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work("S ::= [^a-c\\]-] 'x'..'z';", &alloc);
+        assert!(errors.0.is_empty());
+        let mut classes = vec![];
+        visit(file.rules[0].prod[0], &mut |prod| {
+            if let Prod::Class(class, _) = prod {
+                classes.push(class.clone());
+            }
+        });
+        assert!(classes[0].negated);
+        assert_eq!(classes[0].ranges, vec![('a', 'c'), (']', ']'), ('-', '-')]);
+        assert!(!classes[0].contains('b') && classes[0].contains('d'));
+        assert_eq!(classes[1].ranges, vec![('x', 'z')]);
+        let sentence = file.generate(file, 30);
+        assert!(classes[0].contains(sentence.chars().next().unwrap()));
+        assert!(classes[1].contains(sentence.chars().last().unwrap()));
+    }
+
+    #[test]
+    fn repetition_counts_fit_a_usize() {
+        assert_eq!(
+            messages("S ::= 'a'{99999999999999999999};"),
+            vec!["*** Error at (1,7): repetition count 99999999999999999999 is too large"]
+        );
     }
 }
@@ -1,9 +1,10 @@
 use crate::*;
 use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 
 pub type TermSet<'a> = HashMap<&'a str, BTreeSet<&'a FlatProd<'a>>>;
 
-pub fn first_set<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> TermSet {
+pub fn first_set<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> TermSet<'a> {
     let mut res = HashMap::new();
     loop {
         let mut cur = res.clone();
@@ -13,23 +14,32 @@ pub fn first_set<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> TermSet {
                     .or_insert(BTreeSet::new())
                     .insert(&FlatProd::Eps);
             } else {
+                let mut nullable = true;
                 for prod in rule.prod.iter() {
                     match prod {
-                        FlatProd::Terminal(_) => {
+                        FlatProd::Terminal(_) | FlatProd::Class(_) => {
                             cur.entry(rule.name).or_insert(BTreeSet::new()).insert(prod);
+                            nullable = false;
                             break;
                         }
                         FlatProd::NonTerminal(name) => {
                             let first = cur.entry(name).or_insert(BTreeSet::new()).clone();
                             let cur_first = cur.entry(rule.name).or_insert(BTreeSet::new());
-                            *cur_first = cur_first.union(&first).cloned().collect();
+                            cur_first.extend(first.iter().filter(|p| !p.is_eps()));
                             if !first.contains(&FlatProd::Eps) {
+                                nullable = false;
                                 break;
                             }
                         }
                         _ => {}
                     }
                 }
+                if nullable {
+                    // every symbol derives eps
+                    cur.entry(rule.name)
+                        .or_insert(BTreeSet::new())
+                        .insert(&FlatProd::Eps);
+                }
             }
         }
         if cur == res {
@@ -54,19 +64,17 @@ pub fn follow_set<'a>(rules: &'a Vec<FlatRuleDef<'a>>, first: &TermSet<'a>) -> T
                     for j in (i + 1)..rule.prod.len() {
                         match &rule.prod[j] {
                             FlatProd::NonTerminal(follow_name) => {
-                                let follow = first
-                                    .get(follow_name)
-                                    .expect(&format!("can't find {}", follow_name))
-                                    .clone();
+                                // undefined non terminals derive nothing
+                                let follow = first.get(follow_name).cloned().unwrap_or_default();
                                 let cur_follow = cur.entry(name).or_insert(BTreeSet::new());
                                 *cur_follow = cur_follow.union(&follow).cloned().collect();
                                 cur_follow.remove(&FlatProd::Eps);
-                                if !first.get(follow_name).unwrap().contains(&FlatProd::Eps) {
+                                if !follow.contains(&FlatProd::Eps) {
                                     stop = true;
                                     break;
                                 }
                             }
-                            FlatProd::Terminal(_) => {
+                            FlatProd::Terminal(_) | FlatProd::Class(_) => {
                                 cur.entry(name)
                                     .or_insert(BTreeSet::new())
                                     .insert(rule.prod[j]);
@@ -94,3 +102,588 @@ pub fn follow_set<'a>(rules: &'a Vec<FlatRuleDef<'a>>, first: &TermSet<'a>) -> T
     }
     res
 }
+
+// terminals of a FIRST or FOLLOW set matching a common character
+// at least one of them is a character class, the other a class or a literal of one character
+#[derive(Debug, Clone)]
+pub struct ClassOverlap<'a> {
+    pub set: &'static str,
+    pub non_terminal: &'a str,
+    pub terminals: (&'a str, &'a str),
+    pub example: char,
+}
+
+fn overlap(a: &FlatProd, b: &FlatProd) -> Option<char> {
+    match (a, b) {
+        (FlatProd::Class(a), FlatProd::Class(b)) => a.overlap(b),
+        (FlatProd::Class(class), FlatProd::Terminal(name))
+        | (FlatProd::Terminal(name), FlatProd::Class(class)) => {
+            single_char(name).filter(|ch| class.contains(*ch))
+        }
+        _ => None,
+    }
+}
+
+// set is the name of sets, i.e. FIRST or FOLLOW
+pub fn class_overlaps<'a>(set: &'static str, sets: &TermSet<'a>) -> Vec<ClassOverlap<'a>> {
+    let mut non_terminals: Vec<_> = sets.keys().collect();
+    non_terminals.sort();
+    let mut res = vec![];
+    for non_terminal in non_terminals {
+        let terminals: Vec<_> = sets[non_terminal].iter().collect();
+        for (i, a) in terminals.iter().enumerate() {
+            for b in terminals[i + 1..].iter() {
+                if let Some(example) = overlap(a, b) {
+                    res.push(ClassOverlap {
+                        set,
+                        non_terminal,
+                        terminals: (a.name(), b.name()),
+                        example,
+                    });
+                }
+            }
+        }
+    }
+    res
+}
+
+impl<'a> fmt::Display for ClassOverlap<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}({}): {} and {} both match {:?}",
+            self.set, self.non_terminal, self.terminals.0, self.terminals.1, self.example
+        )
+    }
+}
+
+// FIRST of a sequence of symbols without eps, and whether it is nullable
+fn first_of<'a>(first: &TermSet<'a>, prods: &[&'a FlatProd<'a>]) -> (BTreeSet<&'a str>, bool) {
+    let mut res = BTreeSet::new();
+    for prod in prods.iter() {
+        match prod {
+            FlatProd::Terminal(_) | FlatProd::Class(_) => {
+                res.insert(prod.name());
+                return (res, false);
+            }
+            FlatProd::NonTerminal(name) => {
+                let empty = BTreeSet::new();
+                let first = first.get(name).unwrap_or(&empty);
+                res.extend(first.iter().filter(|p| !p.is_eps()).map(|p| p.name()));
+                if !first.contains(&FlatProd::Eps) {
+                    return (res, false);
+                }
+            }
+            FlatProd::Eps => {}
+        }
+    }
+    (res, true)
+}
+
+// LL(1) predictive table
+#[derive(Debug)]
+pub struct LlTable<'a> {
+    rules: &'a Vec<FlatRuleDef<'a>>,
+    terminals: BTreeSet<&'a str>,
+    // in order of first definition
+    non_terminals: Vec<&'a str>,
+    // (non terminal, terminal) -> (rule index, whether it comes from FOLLOW)
+    entries: HashMap<(&'a str, &'a str), Vec<(usize, bool)>>,
+    // input characters are matched against them
+    classes: Classes<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlConflictKind {
+    FirstFirst,
+    FirstFollow,
+    // a character matched by two terminals predicting different rules
+    Overlap,
+}
+
+// a cell of ll table with more than one rule, or two cells for a character
+#[derive(Debug, Clone)]
+pub struct LlConflict<'a> {
+    pub non_terminal: &'a str,
+    pub terminal: &'a str,
+    pub kind: LlConflictKind,
+    // the other terminal of an overlap and the character both match
+    pub overlap: Option<(&'a str, char)>,
+    pub rules: Vec<&'a FlatRuleDef<'a>>,
+}
+
+pub fn ll1_table<'a>(
+    rules: &'a Vec<FlatRuleDef<'a>>,
+    first: &TermSet<'a>,
+    follow: &TermSet<'a>,
+) -> LlTable<'a> {
+    let mut table = LlTable {
+        rules,
+        terminals: BTreeSet::new(),
+        non_terminals: vec![],
+        entries: HashMap::new(),
+        classes: classes(rules),
+    };
+    table.terminals.insert("#");
+    for (index, rule) in rules.iter().enumerate() {
+        if !table.non_terminals.contains(&rule.name) {
+            table.non_terminals.push(rule.name);
+        }
+        for prod in rule.prod.iter() {
+            if prod.is_terminal() {
+                table.terminals.insert(prod.name());
+            }
+        }
+        let (first_s, nullable) = first_of(first, &rule.prod);
+        for terminal in first_s.into_iter() {
+            table
+                .entries
+                .entry((rule.name, terminal))
+                .or_insert(vec![])
+                .push((index, false));
+        }
+        if nullable {
+            if let Some(follow_s) = follow.get(rule.name) {
+                for terminal in follow_s.iter() {
+                    let entry = table
+                        .entries
+                        .entry((rule.name, terminal.name()))
+                        .or_insert(vec![]);
+                    // the rule may already be there from FIRST
+                    if !entry.iter().any(|(i, _)| *i == index) {
+                        entry.push((index, true));
+                    }
+                }
+            }
+        }
+    }
+    table
+}
+
+impl<'a> LlTable<'a> {
+    pub fn conflicts(&self) -> Vec<LlConflict<'a>> {
+        let mut res = vec![];
+        for non_terminal in self.non_terminals.iter() {
+            for terminal in self.terminals.iter() {
+                let entry = match self.entries.get(&(non_terminal, terminal)) {
+                    Some(entry) if entry.len() > 1 => entry,
+                    _ => continue,
+                };
+                let kind = if entry.iter().filter(|(_, follow)| !follow).count() > 1 {
+                    LlConflictKind::FirstFirst
+                } else {
+                    LlConflictKind::FirstFollow
+                };
+                res.push(LlConflict {
+                    non_terminal,
+                    terminal,
+                    kind,
+                    overlap: None,
+                    rules: entry.iter().map(|(index, _)| &self.rules[*index]).collect(),
+                });
+            }
+
+            // the parser takes a character as the terminal itself before a class containing it
+            let terminals: Vec<&'a str> = self
+                .terminals
+                .iter()
+                .filter(|terminal| self.entries.contains_key(&(non_terminal, terminal)))
+                .cloned()
+                .collect();
+            for (i, a) in terminals.iter().enumerate() {
+                for b in terminals[i + 1..].iter() {
+                    let (a_entry, b_entry) = (
+                        &self.entries[&(*non_terminal, *a)],
+                        &self.entries[&(*non_terminal, *b)],
+                    );
+                    let indices = |entry: &Vec<(usize, bool)>| -> Vec<usize> {
+                        entry.iter().map(|(index, _)| *index).collect()
+                    };
+                    let example = match terminal_overlap(&self.classes, a, b) {
+                        Some(example) if indices(a_entry) != indices(b_entry) => example,
+                        _ => continue,
+                    };
+                    let mut rules = indices(a_entry);
+                    for index in indices(b_entry) {
+                        if !rules.contains(&index) {
+                            rules.push(index);
+                        }
+                    }
+                    res.push(LlConflict {
+                        non_terminal,
+                        terminal: a,
+                        kind: LlConflictKind::Overlap,
+                        overlap: Some((b, example)),
+                        rules: rules.into_iter().map(|index| &self.rules[index]).collect(),
+                    });
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<'a> fmt::Display for LlTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, rule) in self.rules.iter().enumerate() {
+            writeln!(f, "{}: {}", index, rule)?;
+        }
+        write!(f, "NonTerminal\t")?;
+        for terminal in self.terminals.iter() {
+            write!(f, "{}\t", terminal)?;
+        }
+        writeln!(f)?;
+        for non_terminal in self.non_terminals.iter() {
+            write!(f, "{}\t", non_terminal)?;
+            for terminal in self.terminals.iter() {
+                if let Some(entry) = self.entries.get(&(non_terminal, terminal)) {
+                    for (idx, (index, _)) in entry.iter().enumerate() {
+                        if idx > 0 {
+                            write!(f, "/")?;
+                        }
+                        write!(f, "{}", index)?;
+                    }
+                }
+                write!(f, "\t")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> LlConflict<'a> {
+    // the parser takes a character as the terminal itself before a class, so an overlap still has one rule
+    pub fn is_warning(&self) -> bool {
+        self.kind == LlConflictKind::Overlap
+    }
+}
+
+impl<'a> fmt::Display for LlConflict<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            LlConflictKind::FirstFirst => "FIRST/FIRST conflict",
+            LlConflictKind::FirstFollow => "FIRST/FOLLOW conflict",
+            LlConflictKind::Overlap => "warning: overlap",
+        };
+        match self.overlap {
+            Some((other, example)) => writeln!(
+                f,
+                "{} for {} on {} and {}, both matching {:?}:",
+                kind, self.non_terminal, self.terminal, other, example
+            )?,
+            None => writeln!(
+                f,
+                "{} for {} on {}:",
+                kind, self.non_terminal, self.terminal
+            )?,
+        }
+        for rule in self.rules.iter() {
+            writeln!(f, "  {}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LlAction<'a> {
+    Match(&'a str),
+    // expand non terminal with rule
+    Expand(usize),
+    Accept,
+}
+
+// a step of ll parsing, before the action is taken
+#[derive(Debug, Clone)]
+pub struct LlStep<'a> {
+    // top of stack at the end
+    pub stack: Vec<&'a str>,
+    // remaining input ending with "#"
+    pub input: Vec<&'a str>,
+    // None on syntax error
+    pub action: Option<LlAction<'a>>,
+}
+
+// why ll parsing failed
+#[derive(Debug, Clone)]
+pub enum LlError<'a> {
+    // a cell with several rules leaves the parser without a choice
+    NotLl1(Vec<LlConflict<'a>>),
+    Syntax(ParseError<'a>),
+}
+
+impl<'a> fmt::Display for LlError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlError::NotLl1(conflicts) => write!(
+                f,
+                "grammar is not LL(1), its table has {} conflicts",
+                conflicts.len()
+            ),
+            LlError::Syntax(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<'a> LlTable<'a> {
+    // terminals predicting some rule of the non terminal
+    pub fn expected(&self, non_terminal: &str) -> Vec<&'a str> {
+        self.terminals
+            .iter()
+            .filter(|terminal| self.entries.contains_key(&(non_terminal, terminal)))
+            .cloned()
+            .collect()
+    }
+}
+
+// build tree from leftmost derivation
+fn build_tree<'a>(
+    rules: &'a Vec<FlatRuleDef<'a>>,
+    derivation: &mut impl Iterator<Item = usize>,
+    input: &mut impl Iterator<Item = &'a str>,
+) -> ParseTree<'a> {
+    let rule_index = derivation.next().unwrap();
+    let rule = &rules[rule_index];
+    let mut children = vec![];
+    for prod in rule.prod.iter() {
+        match prod {
+            FlatProd::Terminal(_) | FlatProd::Class(_) => {
+                children.push(ParseTree::Terminal(input.next().unwrap()))
+            }
+            FlatProd::NonTerminal(_) => children.push(build_tree(rules, derivation, input)),
+            FlatProd::Eps => {}
+        }
+    }
+    ParseTree::NonTerminal {
+        name: rule.name,
+        rule_index,
+        children,
+    }
+}
+
+// run ll table on terminals, tables with conflicts other than overlaps are refused
+// as expanding a left recursive rule would never consume input
+fn ll_run<'a>(
+    table: &LlTable<'a>,
+    input: &[&'a str],
+    mut on_step: impl FnMut(&[&'a str], usize, Option<&LlAction<'a>>),
+) -> Result<ParseTree<'a>, LlError<'a>> {
+    let conflicts: Vec<LlConflict<'a>> = table
+        .conflicts()
+        .into_iter()
+        .filter(|conflict| !conflict.is_warning())
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(LlError::NotLl1(conflicts));
+    }
+    let rules = table.rules;
+    let mut stack = vec!["#", rules[0].name];
+    let mut derivation = vec![];
+    let mut position = 0;
+    loop {
+        let terminal = input.get(position).cloned().unwrap_or("#");
+        let top = *stack.last().unwrap();
+        let class_match = match (table.classes.get(top), single_char(terminal)) {
+            (Some(class), Some(ch)) => class.contains(ch),
+            _ => false,
+        };
+        let action = if top == "#" && terminal == "#" {
+            Some(LlAction::Accept)
+        } else if top == terminal || class_match {
+            Some(LlAction::Match(terminal))
+        } else {
+            let matched = matching_terminal(&table.classes, terminal, |terminal| {
+                table.entries.contains_key(&(top, terminal))
+            });
+            table
+                .entries
+                .get(&(top, matched))
+                .map(|entry| LlAction::Expand(entry[0].0))
+        };
+        on_step(&stack, position, action.as_ref());
+        match action {
+            Some(LlAction::Accept) => {
+                return Ok(build_tree(
+                    rules,
+                    &mut derivation.into_iter(),
+                    &mut input.iter().cloned(),
+                ));
+            }
+            Some(LlAction::Match(_)) => {
+                stack.pop();
+                position += 1;
+            }
+            Some(LlAction::Expand(rule_index)) => {
+                stack.pop();
+                derivation.push(rule_index);
+                for prod in rules[rule_index].prod.iter().rev() {
+                    if !prod.is_eps() {
+                        stack.push(prod.name());
+                    }
+                }
+            }
+            None => {
+                let expected = if table.non_terminals.contains(&top) {
+                    table.expected(top)
+                } else {
+                    vec![top]
+                };
+                return Err(LlError::Syntax(ParseError {
+                    position,
+                    found: terminal,
+                    expected,
+                }));
+            }
+        }
+    }
+}
+
+pub fn ll_parse<'a>(table: &LlTable<'a>, input: &[&'a str]) -> Result<ParseTree<'a>, LlError<'a>> {
+    ll_run(table, input, |_, _, _| {})
+}
+
+// parse and record every step
+pub fn ll_trace<'a>(
+    table: &LlTable<'a>,
+    input: &[&'a str],
+) -> (Vec<LlStep<'a>>, Result<ParseTree<'a>, LlError<'a>>) {
+    let mut steps = vec![];
+    let res = ll_run(table, input, |stack, position, action| {
+        let mut remaining: Vec<&'a str> = input[position.min(input.len())..].to_vec();
+        remaining.push("#");
+        steps.push(LlStep {
+            stack: stack.to_vec(),
+            input: remaining,
+            action: action.cloned(),
+        });
+    });
+    (steps, res)
+}
+
+pub fn format_ll_trace(table: &LlTable, steps: &[LlStep], format: TraceFormat) -> String {
+    let rows: Vec<Vec<String>> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            vec![
+                (index + 1).to_string(),
+                step.stack.join(" "),
+                step.input.join(" "),
+                match &step.action {
+                    Some(LlAction::Match(terminal)) => format!("match {}", terminal),
+                    Some(LlAction::Expand(rule_index)) => table.rules[*rule_index].to_string(),
+                    Some(LlAction::Accept) => String::from("accept"),
+                    None => String::from("error"),
+                },
+            ]
+        })
+        .collect();
+    format_trace(&["Step", "Stack", "Input", "Action"], &rows, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // flattened rules of code, which must have no errors, and their ll(1) table
+    fn with_table<T>(code: &str, f: impl for<'a> FnOnce(&LlTable<'a>) -> T) -> T {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        let first = first_set(&rules);
+        let follow = follow_set(&rules, &first);
+        f(&ll1_table(&rules, &first, &follow))
+    }
+
+    fn kinds(table: &LlTable) -> Vec<(String, String, LlConflictKind)> {
+        table
+            .conflicts()
+            .iter()
+            .map(|c| (c.non_terminal.to_string(), c.terminal.to_string(), c.kind))
+            .collect()
+    }
+
+    const SUM: &str = "S ::= E; E ::= 'n' T; T ::= '+' 'n' T | _;";
+
+    #[test]
+    fn ll1_table_predicts_rules() {
+        with_table(SUM, |table| {
+            assert!(table.conflicts().is_empty());
+            assert_eq!(table.expected("T"), vec!["#", "'+'"]);
+            assert_eq!(table.entries[&("T", "#")].len(), 1);
+        });
+        with_table("S ::= A; A ::= 'a' 'b' | 'a' 'c';", |table| {
+            let expected = vec![(
+                "A".to_string(),
+                "'a'".to_string(),
+                LlConflictKind::FirstFirst,
+            )];
+            assert_eq!(kinds(table), expected);
+        });
+        // A ::= B is nullable and starts with 'a', which also follows it
+        with_table("S ::= A 'a'; A ::= B; B ::= 'a' | _;", |table| {
+            assert_eq!(table.entries[&("A", "'a'")].len(), 1);
+            let expected = vec![(
+                "B".to_string(),
+                "'a'".to_string(),
+                LlConflictKind::FirstFollow,
+            )];
+            assert_eq!(kinds(table), expected);
+        });
+    }
+
+    #[test]
+    fn ll_parse_follows_the_table() {
+        with_table(SUM, |table| {
+            let tree = ll_parse(table, &["'n'", "'+'", "'n'"]).unwrap();
+            assert_eq!(tree.name(), "S");
+            match ll_parse(table, &["'n'", "'n'"]) {
+                Err(LlError::Syntax(err)) => {
+                    assert_eq!(err.position, 1);
+                    assert_eq!(err.expected, vec!["#", "'+'"]);
+                }
+                res => panic!("{:?}", res),
+            }
+            let (steps, res) = ll_trace(table, &["'n'"]);
+            assert!(res.is_ok());
+            let actions = format_ll_trace(table, &steps, TraceFormat::Plain);
+            assert!(actions.ends_with("5\t#\t#\taccept\n"), "{}", actions);
+        });
+        with_table("S ::= A; A ::= 'a' 'b' | 'a' 'c';", |table| match ll_parse(
+            table,
+            &["'a'", "'b'"],
+        ) {
+            Err(LlError::NotLl1(conflicts)) => assert_eq!(conflicts.len(), 1),
+            res => panic!("{:?}", res),
+        });
+    }
+
+    #[test]
+    fn overlaps_are_warnings() {
+        with_table("S ::= A; A ::= 'a' 'x' | [a-z] 'y';", |table| {
+            let conflicts = table.conflicts();
+            assert_eq!(conflicts.len(), 1);
+            assert!(conflicts[0].is_warning());
+            assert!(ll_parse(table, &["'a'", "'x'"]).is_ok());
+            assert!(ll_parse(table, &["'b'", "'y'"]).is_ok());
+        });
+        let alloc = ASTAlloc::default();
+        let (file, _) = work("S ::= [a-m] | [k-z] | 'k';", &alloc);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        let overlaps: Vec<String> = class_overlaps("FIRST", &first_set(&rules))
+            .iter()
+            .map(|overlap| overlap.to_string())
+            .collect();
+        assert_eq!(
+            overlaps,
+            vec![
+                "FIRST(S): 'k' and [a-m] both match 'k'\n",
+                "FIRST(S): 'k' and [k-z] both match 'k'\n",
+                "FIRST(S): [a-m] and [k-z] both match 'k'\n",
+            ]
+        );
+    }
+}
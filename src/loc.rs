@@ -24,3 +24,60 @@ impl fmt::Debug for Loc {
         write!(f, "({},{})", self.0, self.1)
     }
 }
+
+// byte offset of the start of each line, to find the Loc of a byte offset
+pub struct Lines(Vec<usize>);
+
+impl Lines {
+    pub fn new(code: &str) -> Lines {
+        Lines(
+            std::iter::once(0)
+                .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+        )
+    }
+
+    pub fn loc(&self, code: &str, offset: usize) -> Loc {
+        let line = match self.0.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let col = code[self.0[line]..offset].chars().count();
+        Loc(line as u32 + 1, col as u32 + 1)
+    }
+}
+
+// Span of source text, from start (inclusive) to end (exclusive)
+// lo and hi are the corresponding byte offsets
+#[derive(Copy, Clone, Eq, PartialEq, Default, Ord, PartialOrd)]
+pub struct Span {
+    pub start: Loc,
+    pub end: Loc,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+pub const NO_SPAN: Span = Span {
+    start: NO_LOC,
+    end: NO_LOC,
+    lo: 0,
+    hi: 0,
+};
+
+impl Span {
+    // span covering self and other
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            lo: self.lo,
+            hi: other.hi,
+        }
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}-{:?}", self.start, self.end)
+    }
+}
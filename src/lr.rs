@@ -1,5 +1,5 @@
 use crate::*;
-use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::fmt;
 use std::io::{self, Write};
 
@@ -12,18 +12,30 @@ pub struct LrGraph<'a> {
     non_terminals: BTreeSet<&'a str>,
     first_s: TermSet<'a>,
     follow_s: TermSet<'a>,
+    // input characters are matched against them
+    classes: Classes<'a>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct ProdState {
+    pub position: usize,
+    pub rule_index: usize,
+}
+
+// lr(1) item: lr(0) item with a lookahead terminal
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Lr1ProdState<'a> {
     position: usize,
     rule_index: usize,
+    lookahead: &'a str,
 }
 
 #[derive(Debug, Clone)]
 pub struct LrState<'a> {
     index: usize,
     prods: Vec<ProdState>,
+    // lr(1) items, empty in lr(0) graph
+    lr1_prods: Vec<Lr1ProdState<'a>>,
     edges: Vec<(&'a FlatProd<'a>, usize)>,
 }
 
@@ -32,9 +44,11 @@ pub struct LrState<'a> {
 pub struct LrTable<'a> {
     rows: Vec<LrTableEntry<'a>>,
     graph: &'a LrGraph<'a>,
+    // shift/reduce conflicts removed by precedence
+    resolutions: Vec<LrResolution<'a>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LrAction {
     Shift(usize),
     Reduce(usize),
@@ -93,6 +107,7 @@ pub fn lr_graph<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> LrGraph<'a> {
         non_terminals: BTreeSet::new(),
         first_s,
         follow_s,
+        classes: classes(rules),
     };
     graph.terminals.insert("#");
 
@@ -100,6 +115,7 @@ pub fn lr_graph<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> LrGraph<'a> {
     let mut init_state = LrState {
         index: 0,
         prods: vec![],
+        lr1_prods: vec![],
         edges: vec![],
     };
     init_state.prods = closure(
@@ -113,80 +129,379 @@ pub fn lr_graph<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> LrGraph<'a> {
 
     let mut pending = VecDeque::new();
     pending.push_back(0);
-    loop {
-        if let Some(current) = pending.pop_front() {
-            let state = (*graph
-                .states
-                .iter()
-                .filter(|state| state.index == current)
-                .next()
-                .unwrap())
+    while let Some(current) = pending.pop_front() {
+        let state = graph
+            .states
+            .iter()
+            .find(|state| state.index == current)
+            .unwrap()
             .clone();
-            let mut possible_prods: Vec<&'a FlatProd<'a>> = state
-                .prods
+        let mut possible_prods: Vec<&'a FlatProd<'a>> = state
+            .prods
+            .iter()
+            .filter_map(|prod_state| {
+                let rule = &rules[prod_state.rule_index];
+                if prod_state.position < rule.prod.len() && !rule.prod[prod_state.position].is_eps()
+                {
+                    Some(rule.prod[prod_state.position])
+                } else {
+                    None
+                }
+            })
+            .collect();
+        possible_prods.sort();
+        possible_prods.dedup();
+        for (idx, prod) in possible_prods.into_iter().enumerate() {
+            match prod {
+                FlatProd::Terminal(_) | FlatProd::Class(_) => graph.terminals.insert(prod.name()),
+                FlatProd::NonTerminal(name) => graph.non_terminals.insert(name),
+                FlatProd::Eps => {
+                    assert_eq!(idx, 0);
+                    break;
+                }
+            };
+
+            let mut new_state = LrState {
+                index: nodes,
+                prods: vec![],
+                lr1_prods: vec![],
+                edges: vec![],
+            };
+            for ProdState {
+                position,
+                rule_index,
+            } in state.prods.iter()
+            {
+                let rule = &rules[*rule_index];
+                if *position < rule.prod.len() && rule.prod[*position] == prod {
+                    new_state.prods.push(ProdState {
+                        position: position + 1,
+                        rule_index: *rule_index,
+                    });
+                }
+            }
+            new_state.prods = closure(rules, new_state.prods);
+            let exists = graph
+                .states
                 .iter()
-                .filter_map(|prod_state| {
-                    let rule = &rules[prod_state.rule_index];
-                    if prod_state.position < rule.prod.len()
-                        && !rule.prod[prod_state.position].is_eps()
-                    {
-                        Some(rule.prod[prod_state.position])
-                    } else {
-                        None
+                .find(|state| state.prods == new_state.prods)
+                .map(|state| (*state).clone());
+            if let Some(old) = exists {
+                graph.states[current].edges.push((prod, old.index));
+            } else {
+                nodes += 1;
+                graph.states[current].edges.push((prod, new_state.index));
+                pending.push_back(new_state.index);
+                graph.states.push(new_state);
+            }
+        }
+    }
+
+    graph
+}
+
+// FIRST of `seq` followed by `lookahead`
+fn first_of<'a>(
+    first_s: &TermSet<'a>,
+    seq: &[&'a FlatProd<'a>],
+    lookahead: &'a str,
+) -> BTreeSet<&'a str> {
+    let mut res = BTreeSet::new();
+    for prod in seq.iter() {
+        match prod {
+            FlatProd::Terminal(_) | FlatProd::Class(_) => {
+                res.insert(prod.name());
+                return res;
+            }
+            FlatProd::NonTerminal(name) => {
+                if let Some(first) = first_s.get(name) {
+                    res.extend(first.iter().filter(|f| !f.is_eps()).map(|f| f.name()));
+                    if !first.contains(&FlatProd::Eps) {
+                        return res;
                     }
-                })
-                .collect();
-            possible_prods.sort();
-            possible_prods.dedup();
-            for (idx, prod) in possible_prods.into_iter().enumerate() {
-                match prod {
-                    FlatProd::Terminal(name) => graph.terminals.insert(name),
-                    FlatProd::NonTerminal(name) => graph.non_terminals.insert(name),
-                    FlatProd::Eps => {
-                        assert_eq!(idx, 0);
-                        break;
+                }
+            }
+            FlatProd::Eps => {}
+        }
+    }
+    res.insert(lookahead);
+    res
+}
+
+pub fn lr1_closure<'a>(
+    rules: &'a Vec<FlatRuleDef<'a>>,
+    first_s: &TermSet<'a>,
+    orig: Vec<Lr1ProdState<'a>>,
+) -> Vec<Lr1ProdState<'a>> {
+    let mut res = orig.clone();
+    res.sort();
+    res.dedup();
+    loop {
+        let mut current = res.clone();
+        for Lr1ProdState {
+            position,
+            rule_index,
+            lookahead,
+        } in res.iter()
+        {
+            let rule = &rules[*rule_index];
+            if *position < rule.prod.len() {
+                if let FlatProd::NonTerminal(name) = rule.prod[*position] {
+                    // add all prod of name, for each terminal in FIRST(rest lookahead)
+                    let lookaheads = first_of(first_s, &rule.prod[position + 1..], lookahead);
+                    for (idx, rule) in rules.iter().enumerate() {
+                        if rule.name == *name {
+                            for lookahead in lookaheads.iter() {
+                                current.push(Lr1ProdState {
+                                    position: 0,
+                                    rule_index: idx,
+                                    lookahead,
+                                });
+                            }
+                        }
                     }
-                };
+                }
+            }
+        }
+        current.sort();
+        current.dedup();
+        if res.len() == current.len() {
+            break;
+        }
+        res = current;
+    }
+    res
+}
 
-                let mut new_state = LrState {
-                    index: nodes,
-                    prods: vec![],
-                    edges: vec![],
-                };
-                for ProdState {
-                    position,
-                    rule_index,
-                } in state.prods.iter()
+// lr(0) core of lr(1) items
+fn lr1_core(lr1_prods: &[Lr1ProdState]) -> Vec<ProdState> {
+    let mut res: Vec<ProdState> = lr1_prods
+        .iter()
+        .map(|prod_state| ProdState {
+            position: prod_state.position,
+            rule_index: prod_state.rule_index,
+        })
+        .collect();
+    res.sort();
+    res.dedup();
+    res
+}
+
+// canonical lr(1) graph, states are distinguished by lookaheads as well
+pub fn lr1_graph<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> LrGraph<'a> {
+    let first_s = first_set(rules);
+    let follow_s = follow_set(rules, &first_s).clone();
+    let mut graph = LrGraph {
+        rules,
+        states: vec![],
+        terminals: BTreeSet::new(),
+        non_terminals: BTreeSet::new(),
+        first_s,
+        follow_s,
+        classes: classes(rules),
+    };
+    graph.terminals.insert("#");
+
+    let lr1_prods = lr1_closure(
+        rules,
+        &graph.first_s,
+        vec![Lr1ProdState {
+            position: 0,
+            rule_index: 0,
+            lookahead: "#",
+        }],
+    );
+    graph.states.push(LrState {
+        index: 0,
+        prods: lr1_core(&lr1_prods),
+        lr1_prods,
+        edges: vec![],
+    });
+
+    let mut pending = VecDeque::new();
+    pending.push_back(0);
+    while let Some(current) = pending.pop_front() {
+        let state = graph.states[current].clone();
+        let mut possible_prods: Vec<&'a FlatProd<'a>> = state
+            .lr1_prods
+            .iter()
+            .filter_map(|prod_state| {
+                let rule = &rules[prod_state.rule_index];
+                if prod_state.position < rule.prod.len() && !rule.prod[prod_state.position].is_eps()
                 {
-                    let rule = &rules[*rule_index];
-                    if *position < rule.prod.len() && rule.prod[*position] == prod {
-                        new_state.prods.push(ProdState {
-                            position: position + 1,
-                            rule_index: *rule_index,
-                        });
-                    }
+                    Some(rule.prod[prod_state.position])
+                } else {
+                    None
+                }
+            })
+            .collect();
+        possible_prods.sort();
+        possible_prods.dedup();
+        for prod in possible_prods.into_iter() {
+            match prod {
+                FlatProd::Terminal(_) | FlatProd::Class(_) => graph.terminals.insert(prod.name()),
+                FlatProd::NonTerminal(name) => graph.non_terminals.insert(name),
+                FlatProd::Eps => unreachable!(),
+            };
+
+            let mut lr1_prods = vec![];
+            for Lr1ProdState {
+                position,
+                rule_index,
+                lookahead,
+            } in state.lr1_prods.iter()
+            {
+                let rule = &rules[*rule_index];
+                if *position < rule.prod.len() && rule.prod[*position] == prod {
+                    lr1_prods.push(Lr1ProdState {
+                        position: position + 1,
+                        rule_index: *rule_index,
+                        lookahead,
+                    });
+                }
+            }
+            let lr1_prods = lr1_closure(rules, &graph.first_s, lr1_prods);
+            let exists = graph
+                .states
+                .iter()
+                .find(|state| state.lr1_prods == lr1_prods)
+                .map(|state| state.index);
+            if let Some(old) = exists {
+                graph.states[current].edges.push((prod, old));
+            } else {
+                let index = graph.states.len();
+                graph.states[current].edges.push((prod, index));
+                pending.push_back(index);
+                graph.states.push(LrState {
+                    index,
+                    prods: lr1_core(&lr1_prods),
+                    lr1_prods,
+                    edges: vec![],
+                });
+            }
+        }
+    }
+
+    graph
+}
+
+// placeholder lookahead used to detect propagation
+const PROPAGATE: &str = "$";
+
+// lalr(1) items of every state in an lr(0) graph, computed by lookahead propagation
+pub fn lalr1_prods<'a>(graph: &LrGraph<'a>) -> Vec<Vec<Lr1ProdState<'a>>> {
+    let rules = graph.rules;
+    let is_kernel = |state: &LrState, prod_state: &ProdState| {
+        prod_state.position > 0 || (state.index == 0 && prod_state.rule_index == 0)
+    };
+    let mut lookaheads: BTreeMap<(usize, ProdState), BTreeSet<&'a str>> = BTreeMap::new();
+    let mut propagate: BTreeMap<(usize, ProdState), Vec<(usize, ProdState)>> = BTreeMap::new();
+    lookaheads
+        .entry((
+            0,
+            ProdState {
+                position: 0,
+                rule_index: 0,
+            },
+        ))
+        .or_default()
+        .insert("#");
+
+    // spontaneous lookaheads and propagation links
+    for state in graph.states.iter() {
+        for kernel in state.prods.iter().filter(|p| is_kernel(state, p)) {
+            let closure = lr1_closure(
+                rules,
+                &graph.first_s,
+                vec![Lr1ProdState {
+                    position: kernel.position,
+                    rule_index: kernel.rule_index,
+                    lookahead: PROPAGATE,
+                }],
+            );
+            for Lr1ProdState {
+                position,
+                rule_index,
+                lookahead,
+            } in closure.into_iter()
+            {
+                let rule = &rules[rule_index];
+                if position >= rule.prod.len() || rule.prod[position].is_eps() {
+                    continue;
                 }
-                new_state.prods = closure(rules, new_state.prods);
-                let exists = graph
-                    .states
+                let (_, next) = state
+                    .edges
                     .iter()
-                    .find(|state| state.prods == new_state.prods)
-                    .map(|state| (*state).clone());
-                if let Some(old) = exists {
-                    graph.states[current].edges.push((prod, old.index));
+                    .find(|(prod, _)| *prod == rule.prod[position])
+                    .unwrap();
+                let target = (
+                    *next,
+                    ProdState {
+                        position: position + 1,
+                        rule_index,
+                    },
+                );
+                if lookahead == PROPAGATE {
+                    propagate
+                        .entry((state.index, kernel.clone()))
+                        .or_insert(vec![])
+                        .push(target);
                 } else {
-                    nodes += 1;
-                    graph.states[current].edges.push((prod, new_state.index));
-                    pending.push_back(new_state.index);
-                    graph.states.push(new_state);
+                    lookaheads.entry(target).or_default().insert(lookahead);
                 }
             }
-        } else {
+        }
+    }
+
+    // propagate until nothing changes
+    loop {
+        let mut changed = false;
+        for (from, targets) in propagate.iter() {
+            let current = lookaheads.get(from).cloned().unwrap_or_default();
+            for target in targets.iter() {
+                let set = lookaheads.entry(target.clone()).or_default();
+                let len = set.len();
+                set.extend(current.iter());
+                changed |= set.len() != len;
+            }
+        }
+        if !changed {
             break;
         }
     }
 
     graph
+        .states
+        .iter()
+        .map(|state| {
+            let mut kernels = vec![];
+            for kernel in state.prods.iter().filter(|p| is_kernel(state, p)) {
+                if let Some(set) = lookaheads.get(&(state.index, kernel.clone())) {
+                    for lookahead in set.iter() {
+                        kernels.push(Lr1ProdState {
+                            position: kernel.position,
+                            rule_index: kernel.rule_index,
+                            lookahead,
+                        });
+                    }
+                }
+            }
+            lr1_closure(rules, &graph.first_s, kernels)
+        })
+        .collect()
+}
+
+impl<'a> LrState<'a> {
+    // lookaheads of an lr(0) item in this state
+    fn lookaheads(&self, prod_state: &ProdState) -> Vec<&'a str> {
+        self.lr1_prods
+            .iter()
+            .filter(|lr1| {
+                lr1.position == prod_state.position && lr1.rule_index == prod_state.rule_index
+            })
+            .map(|lr1| lr1.lookahead)
+            .collect()
+    }
 }
 
 impl<'a> fmt::Display for LrGraph<'a> {
@@ -205,8 +520,8 @@ impl<'a> fmt::Display for LrGraph<'a> {
                         write!(f, " .")?;
                     }
                     match prod {
-                        FlatProd::Terminal(name) | FlatProd::NonTerminal(name) => {
-                            write!(f, " {}", name)?
+                        FlatProd::Terminal(_) | FlatProd::Class(_) | FlatProd::NonTerminal(_) => {
+                            write!(f, " {}", prod.name())?
                         }
                         FlatProd::Eps => {
                             assert_eq!(idx, 0);
@@ -216,14 +531,21 @@ impl<'a> fmt::Display for LrGraph<'a> {
                 if rule.prod.len() == *position {
                     write!(f, " .")?;
                 }
+                let lookaheads = state.lookaheads(&ProdState {
+                    position: *position,
+                    rule_index: *rule_index,
+                });
+                if !lookaheads.is_empty() {
+                    write!(f, " [{}]", lookaheads.join("/"))?;
+                }
                 write!(f, ", ")?;
             }
             writeln!(f)?;
             write!(f, "Edges: ")?;
             for (prod, next_state) in state.edges.iter() {
                 match prod {
-                    FlatProd::Terminal(name) | FlatProd::NonTerminal(name) => {
-                        write!(f, " {} -> {}", name, next_state)?
+                    FlatProd::Terminal(_) | FlatProd::Class(_) | FlatProd::NonTerminal(_) => {
+                        write!(f, " {} -> {}", prod.name(), next_state)?
                     }
                     FlatProd::Eps => write!(f, " _ -> {}", next_state)?,
                 }
@@ -235,6 +557,11 @@ impl<'a> fmt::Display for LrGraph<'a> {
 }
 
 impl<'a> LrGraph<'a> {
+    // terminals of the grammar, with # for the end of input
+    pub fn terminals(&self) -> &BTreeSet<&'a str> {
+        &self.terminals
+    }
+
     pub fn print_dot(&self) -> io::Result<String> {
         let res: Vec<u8> = Vec::new();
         let mut f = io::Cursor::new(res);
@@ -253,8 +580,8 @@ impl<'a> LrGraph<'a> {
                         write!(f, " .")?;
                     }
                     match prod {
-                        FlatProd::Terminal(name) | FlatProd::NonTerminal(name) => {
-                            write!(f, " {}", name)?
+                        FlatProd::Terminal(_) | FlatProd::Class(_) | FlatProd::NonTerminal(_) => {
+                            write!(f, " {}", prod.name())?
                         }
                         FlatProd::Eps => write!(f, " _")?,
                     }
@@ -262,13 +589,26 @@ impl<'a> LrGraph<'a> {
                 if rule.prod.len() == *position {
                     write!(f, " .")?;
                 }
+                let lookaheads = state.lookaheads(&ProdState {
+                    position: *position,
+                    rule_index: *rule_index,
+                });
+                if !lookaheads.is_empty() {
+                    write!(f, ", {}", lookaheads.join("/"))?;
+                }
                 write!(f, "\\n")?;
             }
             writeln!(f, "\"]")?;
             for (prod, next_state) in state.edges.iter() {
                 match prod {
-                    FlatProd::Terminal(name) | FlatProd::NonTerminal(name) => {
-                        writeln!(f, "{} -> {} [label=\"{}\"]", state.index, next_state, name)?
+                    FlatProd::Terminal(_) | FlatProd::Class(_) | FlatProd::NonTerminal(_) => {
+                        writeln!(
+                            f,
+                            "{} -> {} [label=\"{}\"]",
+                            state.index,
+                            next_state,
+                            prod.name()
+                        )?
                     }
                     FlatProd::Eps => {
                         writeln!(f, "{} -> {} [label=\"_\"]", state.index, next_state)?
@@ -281,7 +621,8 @@ impl<'a> LrGraph<'a> {
     }
 }
 
-pub fn lr0_table<'a>(graph: &'a LrGraph<'a>) -> LrTable<'a> {
+// shift and goto are the same for all kinds of lr tables
+fn shift_goto_rows<'a>(graph: &'a LrGraph<'a>) -> Vec<LrTableEntry<'a>> {
     let mut rows = vec![
         LrTableEntry {
             actions: HashMap::new(),
@@ -296,24 +637,37 @@ pub fn lr0_table<'a>(graph: &'a LrGraph<'a>) -> LrTable<'a> {
                     // goto
                     rows[state.index].goto.insert(name, *index);
                 }
-                FlatProd::Terminal(name) => {
+                FlatProd::Terminal(_) | FlatProd::Class(_) => {
                     // shift
                     rows[state.index]
                         .actions
-                        .entry(name)
+                        .entry(prod.name())
                         .or_insert(vec![])
                         .push(LrAction::Shift(*index));
                 }
-                _ => unimplemented!(),
+                // edges are only made for the symbol after the dot, which is never eps
+                FlatProd::Eps => unreachable!(),
             }
         }
+    }
+    rows
+}
+
+// whether the dot is at the end of the rule
+fn reducible(rule: &FlatRuleDef, position: usize) -> bool {
+    position == rule.prod.len() || (rule.prod.len() == 1 && rule.prod[0].is_eps())
+}
+
+pub fn lr0_table<'a>(graph: &'a LrGraph<'a>, precs: &[PrecDef]) -> LrTable<'a> {
+    let mut rows = shift_goto_rows(graph);
+    for state in graph.states.iter() {
         for ProdState {
             position,
             rule_index,
         } in state.prods.iter()
         {
             let rule = &graph.rules[*rule_index];
-            if *position == rule.prod.len() || (rule.prod.len() == 1 && rule.prod[0].is_eps()) {
+            if reducible(rule, *position) {
                 // prod
                 if *rule_index == 0 {
                     // accept
@@ -335,42 +689,19 @@ pub fn lr0_table<'a>(graph: &'a LrGraph<'a>) -> LrTable<'a> {
             }
         }
     }
-    LrTable { rows, graph }
+    LrTable::resolved(rows, graph, precs)
 }
 
-pub fn slr1_table<'a>(graph: &'a LrGraph<'a>) -> LrTable<'a> {
-    let mut rows = vec![
-        LrTableEntry {
-            actions: HashMap::new(),
-            goto: HashMap::new()
-        };
-        graph.states.len()
-    ];
+pub fn slr1_table<'a>(graph: &'a LrGraph<'a>, precs: &[PrecDef]) -> LrTable<'a> {
+    let mut rows = shift_goto_rows(graph);
     for state in graph.states.iter() {
-        for (prod, index) in state.edges.iter() {
-            match prod {
-                FlatProd::NonTerminal(name) => {
-                    // goto
-                    rows[state.index].goto.insert(name, *index);
-                }
-                FlatProd::Terminal(name) => {
-                    // shift
-                    rows[state.index]
-                        .actions
-                        .entry(name)
-                        .or_insert(vec![])
-                        .push(LrAction::Shift(*index));
-                }
-                _ => unimplemented!(),
-            }
-        }
         for ProdState {
             position,
             rule_index,
         } in state.prods.iter()
         {
             let rule = &graph.rules[*rule_index];
-            if *position == rule.prod.len() || (rule.prod.len() == 1 && rule.prod[0].is_eps()) {
+            if reducible(rule, *position) {
                 // prod
                 if *rule_index == 0 {
                     // accept
@@ -384,7 +715,7 @@ pub fn slr1_table<'a>(graph: &'a LrGraph<'a>) -> LrTable<'a> {
                     for prod in graph.follow_s.get(rule.name).unwrap() {
                         rows[state.index]
                             .actions
-                            .entry(prod.name().clone())
+                            .entry(prod.name())
                             .or_insert(vec![])
                             .push(LrAction::Reduce(*rule_index));
                     }
@@ -392,7 +723,61 @@ pub fn slr1_table<'a>(graph: &'a LrGraph<'a>) -> LrTable<'a> {
             }
         }
     }
-    LrTable { rows, graph }
+    LrTable::resolved(rows, graph, precs)
+}
+
+// reduce on lookaheads of lr(1) items
+fn lookahead_table<'a>(
+    graph: &'a LrGraph<'a>,
+    lr1_prods: Vec<&[Lr1ProdState<'a>]>,
+    precs: &[PrecDef],
+) -> LrTable<'a> {
+    let mut rows = shift_goto_rows(graph);
+    for (index, prods) in lr1_prods.into_iter().enumerate() {
+        for Lr1ProdState {
+            position,
+            rule_index,
+            lookahead,
+        } in prods.iter()
+        {
+            let rule = &graph.rules[*rule_index];
+            if reducible(rule, *position) {
+                if *rule_index == 0 && *lookahead == "#" {
+                    // accept
+                    rows[index]
+                        .actions
+                        .entry("#")
+                        .or_insert(vec![])
+                        .push(LrAction::Accept);
+                } else {
+                    // reduce only for the lookahead
+                    rows[index]
+                        .actions
+                        .entry(lookahead)
+                        .or_insert(vec![])
+                        .push(LrAction::Reduce(*rule_index));
+                }
+            }
+        }
+    }
+    LrTable::resolved(rows, graph, precs)
+}
+
+// graph must be built by lr1_graph
+pub fn lr1_table<'a>(graph: &'a LrGraph<'a>, precs: &[PrecDef]) -> LrTable<'a> {
+    let lr1_prods = graph
+        .states
+        .iter()
+        .map(|state| &state.lr1_prods[..])
+        .collect();
+    lookahead_table(graph, lr1_prods, precs)
+}
+
+// graph must be built by lr_graph
+pub fn lalr1_table<'a>(graph: &'a LrGraph<'a>, precs: &[PrecDef]) -> LrTable<'a> {
+    let lr1_prods = lalr1_prods(graph);
+    let lr1_prods = lr1_prods.iter().map(|prods| &prods[..]).collect();
+    lookahead_table(graph, lr1_prods, precs)
 }
 
 impl<'a> fmt::Display for LrTable<'a> {
@@ -430,7 +815,7 @@ impl<'a> fmt::Display for LrTable<'a> {
     }
 }
 
-impl<'a> fmt::Display for LrAction {
+impl fmt::Display for LrAction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LrAction::Shift(s) => write!(f, "s{}", s),
@@ -439,3 +824,714 @@ impl<'a> fmt::Display for LrAction {
         }
     }
 }
+
+impl<'a> LrTable<'a> {
+    // terminals with an action in the state
+    pub fn expected(&self, state: usize) -> Vec<&'a str> {
+        let mut res: Vec<&'a str> = self.rows[state]
+            .actions
+            .iter()
+            .filter(|(_, actions)| !actions.is_empty())
+            .map(|(terminal, _)| *terminal)
+            .collect();
+        res.sort();
+        res
+    }
+}
+
+// a step of lr parsing, before the action is taken
+#[derive(Debug, Clone)]
+pub struct LrStep<'a> {
+    pub states: Vec<usize>,
+    pub symbols: Vec<&'a str>,
+    // remaining input ending with "#"
+    pub input: Vec<&'a str>,
+    // None on syntax error
+    pub action: Option<LrAction>,
+}
+
+// run lr table on terminals, conflicts are resolved by taking the first action
+// and a terminal over a class containing its character
+fn lr_run<'a>(
+    table: &LrTable<'a>,
+    input: &[&'a str],
+    mut on_step: impl FnMut(&[usize], &[ParseTree<'a>], usize, Option<&LrAction>),
+) -> Result<ParseTree<'a>, ParseError<'a>> {
+    let rules = table.graph.rules;
+    let mut states = vec![0];
+    let mut symbols = vec![];
+    let mut position = 0;
+    loop {
+        let terminal = input.get(position).cloned().unwrap_or("#");
+        let state = *states.last().unwrap();
+        let actions = &table.rows[state].actions;
+        let matched = matching_terminal(&table.graph.classes, terminal, |terminal| {
+            actions
+                .get(terminal)
+                .is_some_and(|actions| !actions.is_empty())
+        });
+        let action = actions.get(matched).and_then(|actions| actions.first());
+        on_step(&states, &symbols, position, action);
+        match action {
+            Some(LrAction::Shift(next)) => {
+                states.push(*next);
+                symbols.push(ParseTree::Terminal(terminal));
+                position += 1;
+            }
+            Some(LrAction::Reduce(rule_index)) => {
+                let rule = &rules[*rule_index];
+                let len = if rule.prod[0].is_eps() {
+                    0
+                } else {
+                    rule.prod.len()
+                };
+                states.truncate(states.len() - len);
+                let children = symbols.split_off(symbols.len() - len);
+                let next = table.rows[*states.last().unwrap()].goto[rule.name];
+                states.push(next);
+                symbols.push(ParseTree::NonTerminal {
+                    name: rule.name,
+                    rule_index: *rule_index,
+                    children,
+                });
+            }
+            Some(LrAction::Accept) => {
+                return Ok(ParseTree::NonTerminal {
+                    name: rules[0].name,
+                    rule_index: 0,
+                    children: symbols,
+                });
+            }
+            None => {
+                return Err(ParseError {
+                    position,
+                    found: terminal,
+                    expected: table.expected(state),
+                });
+            }
+        }
+    }
+}
+
+pub fn lr_parse<'a>(
+    table: &LrTable<'a>,
+    input: &[&'a str],
+) -> Result<ParseTree<'a>, ParseError<'a>> {
+    lr_run(table, input, |_, _, _, _| {})
+}
+
+// terminals the table accepts after input, none if input has a syntax error
+pub fn lr_expected<'a>(table: &LrTable<'a>, input: &[&'a str]) -> Vec<&'a str> {
+    let mut res = vec![];
+    let mut reached = false;
+    let _ = lr_run(table, input, |states, _, position, _| {
+        // the state right after the last terminal, before reducing on the end of input
+        if position == input.len() && !reached {
+            res = table.expected(*states.last().unwrap());
+            reached = true;
+        }
+    });
+    res
+}
+
+// parse and record every step
+pub fn lr_trace<'a>(
+    table: &LrTable<'a>,
+    input: &[&'a str],
+) -> (Vec<LrStep<'a>>, Result<ParseTree<'a>, ParseError<'a>>) {
+    let mut steps = vec![];
+    let res = lr_run(table, input, |states, symbols, position, action| {
+        let mut remaining: Vec<&'a str> = input[position.min(input.len())..].to_vec();
+        remaining.push("#");
+        steps.push(LrStep {
+            states: states.to_vec(),
+            symbols: symbols.iter().map(|symbol| symbol.name()).collect(),
+            input: remaining,
+            action: action.cloned(),
+        });
+    });
+    (steps, res)
+}
+
+pub fn format_lr_trace(steps: &[LrStep], format: TraceFormat) -> String {
+    let rows: Vec<Vec<String>> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let states: Vec<String> = step.states.iter().map(|s| s.to_string()).collect();
+            vec![
+                (index + 1).to_string(),
+                states.join(" "),
+                step.symbols.join(" "),
+                step.input.join(" "),
+                match &step.action {
+                    Some(action) => action.to_string(),
+                    None => String::from("error"),
+                },
+            ]
+        })
+        .collect();
+    format_trace(
+        &["Step", "States", "Symbols", "Input", "Action"],
+        &rows,
+        format,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+    // a character matched by two terminals with different actions
+    Overlap,
+}
+
+// a cell of lr table with more than one action, or two cells for a character
+#[derive(Debug, Clone)]
+pub struct LrConflict<'a> {
+    pub state: usize,
+    pub terminal: &'a str,
+    pub kind: ConflictKind,
+    // the other terminal of an overlap and the character both match
+    pub overlap: Option<(&'a str, char)>,
+    pub actions: Vec<LrAction>,
+    // items in the state causing the actions
+    pub items: Vec<(ProdState, LrAction)>,
+    // shortest input reaching the state
+    pub prefix: Vec<&'a str>,
+    // input after the prefix, starting with the terminal
+    pub continuation: Vec<&'a str>,
+    rules: &'a Vec<FlatRuleDef<'a>>,
+}
+
+// shortest terminal string derived from each non terminal
+fn shortest_derivations<'a>(rules: &'a Vec<FlatRuleDef<'a>>) -> HashMap<&'a str, Vec<&'a str>> {
+    let mut res: HashMap<&'a str, Vec<&'a str>> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for rule in rules.iter() {
+            let mut derived = vec![];
+            let mut complete = true;
+            for prod in rule.prod.iter() {
+                match prod {
+                    FlatProd::Terminal(_) | FlatProd::Class(_) => derived.push(prod.name()),
+                    FlatProd::NonTerminal(name) => {
+                        if let Some(d) = res.get(name) {
+                            derived.extend(d.iter());
+                        } else {
+                            complete = false;
+                            break;
+                        }
+                    }
+                    FlatProd::Eps => {}
+                }
+            }
+            if complete && res.get(rule.name).is_none_or(|d| d.len() > derived.len()) {
+                res.insert(rule.name, derived);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    res
+}
+
+// expand symbols into terminals with shortest derivations
+fn expand<'a>(
+    prods: &[&'a FlatProd<'a>],
+    shortest: &HashMap<&'a str, Vec<&'a str>>,
+) -> Vec<&'a str> {
+    let mut res = vec![];
+    for prod in prods.iter() {
+        match prod {
+            FlatProd::Terminal(_) | FlatProd::Class(_) => res.push(prod.name()),
+            FlatProd::NonTerminal(name) => match shortest.get(name) {
+                Some(d) => res.extend(d.iter()),
+                None => res.push(*name),
+            },
+            FlatProd::Eps => {}
+        }
+    }
+    res
+}
+
+impl<'a> LrGraph<'a> {
+    // shortest symbol path from the initial state to every state
+    fn shortest_paths(&self) -> Vec<Vec<&'a FlatProd<'a>>> {
+        let mut res: Vec<Option<Vec<&'a FlatProd<'a>>>> = vec![None; self.states.len()];
+        res[0] = Some(vec![]);
+        let mut pending = VecDeque::new();
+        pending.push_back(0);
+        while let Some(current) = pending.pop_front() {
+            for (prod, next) in self.states[current].edges.iter() {
+                if res[*next].is_none() {
+                    let mut path = res[current].clone().unwrap();
+                    path.push(prod);
+                    res[*next] = Some(path);
+                    pending.push_back(*next);
+                }
+            }
+        }
+        res.into_iter()
+            .map(|path| path.unwrap_or_default())
+            .collect()
+    }
+}
+
+impl<'a> LrTable<'a> {
+    // items of the state causing actions on terminal, and the shortest input after the state
+    fn items(
+        &self,
+        index: usize,
+        terminal: &'a str,
+        actions: &[LrAction],
+        shortest: &HashMap<&'a str, Vec<&'a str>>,
+    ) -> (Vec<(ProdState, LrAction)>, Vec<&'a str>) {
+        let graph = self.graph;
+        let mut items = vec![];
+        let mut continuation = vec![terminal];
+        for prod_state in graph.states[index].prods.iter() {
+            let rule = &graph.rules[prod_state.rule_index];
+            let action = if reducible(rule, prod_state.position) {
+                if prod_state.rule_index == 0 && terminal == "#" {
+                    LrAction::Accept
+                } else {
+                    LrAction::Reduce(prod_state.rule_index)
+                }
+            } else if rule.prod[prod_state.position].is_terminal()
+                && rule.prod[prod_state.position].name() == terminal
+            {
+                // precedence may have removed the shift
+                let next = match actions
+                    .iter()
+                    .find(|action| matches!(action, LrAction::Shift(_)))
+                {
+                    Some(next) => next,
+                    None => continue,
+                };
+                if continuation.len() == 1 {
+                    continuation.extend(expand(&rule.prod[prod_state.position + 1..], shortest));
+                }
+                next.clone()
+            } else {
+                continue;
+            };
+            if actions.contains(&action) {
+                items.push((prod_state.clone(), action));
+            }
+        }
+        (items, continuation)
+    }
+
+    pub fn conflicts(&self) -> Vec<LrConflict<'a>> {
+        let graph = self.graph;
+        let shortest = shortest_derivations(graph.rules);
+        let paths = graph.shortest_paths();
+        let mut res = vec![];
+        for (index, row) in self.rows.iter().enumerate() {
+            for terminal in graph.terminals.iter() {
+                let actions = match row.actions.get(terminal) {
+                    Some(actions) if actions.len() > 1 => actions,
+                    _ => continue,
+                };
+                let (items, continuation) = self.items(index, terminal, actions, &shortest);
+                let kind = if actions
+                    .iter()
+                    .any(|action| matches!(action, LrAction::Shift(_)))
+                {
+                    ConflictKind::ShiftReduce
+                } else {
+                    ConflictKind::ReduceReduce
+                };
+                res.push(LrConflict {
+                    state: index,
+                    terminal,
+                    kind,
+                    overlap: None,
+                    actions: actions.clone(),
+                    items,
+                    prefix: expand(&paths[index], &shortest),
+                    continuation,
+                    rules: graph.rules,
+                });
+            }
+
+            // the parser takes a character as the terminal itself before a class containing it
+            let terminals: Vec<&'a str> = graph
+                .terminals
+                .iter()
+                .filter(|terminal| row.actions.get(*terminal).is_some_and(|a| !a.is_empty()))
+                .cloned()
+                .collect();
+            for (i, a) in terminals.iter().enumerate() {
+                for b in terminals[i + 1..].iter() {
+                    let example = match terminal_overlap(&graph.classes, a, b) {
+                        Some(example) if row.actions[a] != row.actions[b] => example,
+                        _ => continue,
+                    };
+                    let (mut items, continuation) =
+                        self.items(index, a, &row.actions[a], &shortest);
+                    items.extend(self.items(index, b, &row.actions[b], &shortest).0);
+                    let mut actions = row.actions[a].clone();
+                    actions.extend(row.actions[b].iter().cloned());
+                    res.push(LrConflict {
+                        state: index,
+                        terminal: a,
+                        kind: ConflictKind::Overlap,
+                        overlap: Some((b, example)),
+                        actions,
+                        items,
+                        prefix: expand(&paths[index], &shortest),
+                        continuation,
+                        rules: graph.rules,
+                    });
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<'a> LrConflict<'a> {
+    // the parser takes a character as the terminal itself before a class, so an overlap still has one action
+    pub fn is_warning(&self) -> bool {
+        self.kind == ConflictKind::Overlap
+    }
+}
+
+impl<'a> fmt::Display for LrConflict<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::ShiftReduce => "shift/reduce conflict",
+            ConflictKind::ReduceReduce => "reduce/reduce conflict",
+            ConflictKind::Overlap => "warning: overlap",
+        };
+        match self.overlap {
+            Some((other, example)) => writeln!(
+                f,
+                "{} in state {} on {} and {}, both matching {:?}:",
+                kind, self.state, self.terminal, other, example
+            )?,
+            None => writeln!(f, "{} in state {} on {}:", kind, self.state, self.terminal)?,
+        }
+        for (
+            ProdState {
+                position,
+                rule_index,
+            },
+            action,
+        ) in self.items.iter()
+        {
+            let rule = &self.rules[*rule_index];
+            write!(f, "  {}: {} ::=", action, rule.name)?;
+            for (idx, prod) in rule.prod.iter().enumerate() {
+                if idx == *position {
+                    write!(f, " .")?;
+                }
+                match prod {
+                    FlatProd::Terminal(_) | FlatProd::Class(_) | FlatProd::NonTerminal(_) => {
+                        write!(f, " {}", prod.name())?
+                    }
+                    FlatProd::Eps => {}
+                }
+            }
+            if rule.prod.len() == *position {
+                write!(f, " .")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "  Example:")?;
+        for name in self.prefix.iter() {
+            write!(f, " {}", name)?;
+        }
+        write!(f, " \u{2022}")?;
+        for name in self.continuation.iter() {
+            write!(f, " {}", name)?;
+        }
+        writeln!(f)
+    }
+}
+
+// a shift/reduce conflict resolved by precedence
+#[derive(Debug, Clone)]
+pub struct LrResolution<'a> {
+    pub state: usize,
+    pub terminal: &'a str,
+    pub shift: usize,
+    pub rule_index: usize,
+    // None if both are removed for non associative terminal
+    pub chosen: Option<LrAction>,
+    pub reason: &'static str,
+}
+
+// precedence level (counting from 1) and associativity of a terminal
+fn term_prec(precs: &[PrecDef], name: &str) -> Option<(usize, Assoc)> {
+    precs
+        .iter()
+        .enumerate()
+        .find(|(_, prec)| prec.terms.contains(&name))
+        .map(|(level, prec)| (level + 1, prec.assoc))
+}
+
+// precedence of a rule is the precedence of its last terminal
+fn rule_prec(precs: &[PrecDef], rule: &FlatRuleDef) -> Option<(usize, Assoc)> {
+    rule.prod
+        .iter()
+        .rev()
+        .filter_map(|prod| match prod {
+            FlatProd::Terminal(_) | FlatProd::Class(_) => Some(prod.name()),
+            _ => None,
+        })
+        .next()
+        .and_then(|name| term_prec(precs, name))
+}
+
+impl<'a> LrTable<'a> {
+    // table of rows with shift/reduce conflicts resolved by precedence
+    fn resolved(rows: Vec<LrTableEntry<'a>>, graph: &'a LrGraph<'a>, precs: &[PrecDef]) -> Self {
+        let mut table = LrTable {
+            rows,
+            graph,
+            resolutions: vec![],
+        };
+        table.resolve(precs);
+        table
+    }
+
+    // resolve shift/reduce conflicts like yacc, each reduce is weighed against the shift
+    // reduce/reduce conflicts are kept
+    fn resolve(&mut self, precs: &[PrecDef]) {
+        let mut res = vec![];
+        for (index, row) in self.rows.iter_mut().enumerate() {
+            for (terminal, actions) in row.actions.iter_mut() {
+                let shift = match actions.iter().find_map(|action| match action {
+                    LrAction::Shift(s) => Some(*s),
+                    _ => None,
+                }) {
+                    Some(shift) if actions.len() > 1 => shift,
+                    _ => continue,
+                };
+                let term = match term_prec(precs, terminal) {
+                    Some(prec) => prec,
+                    None => continue,
+                };
+                let mut keep_shift = true;
+                let mut dropped = vec![];
+                for action in actions.iter() {
+                    let rule_index = match action {
+                        LrAction::Reduce(r) => *r,
+                        _ => continue,
+                    };
+                    let rule = match rule_prec(precs, &self.graph.rules[rule_index]) {
+                        Some(prec) => prec,
+                        None => continue,
+                    };
+                    let (chosen, reason) = if rule.0 > term.0 {
+                        (
+                            Some(LrAction::Reduce(rule_index)),
+                            "rule has higher precedence",
+                        )
+                    } else if rule.0 < term.0 {
+                        (
+                            Some(LrAction::Shift(shift)),
+                            "terminal has higher precedence",
+                        )
+                    } else {
+                        match term.1 {
+                            Assoc::Left => (Some(LrAction::Reduce(rule_index)), "left associative"),
+                            Assoc::Right => (Some(LrAction::Shift(shift)), "right associative"),
+                            Assoc::NonAssoc => (None, "non associative"),
+                        }
+                    };
+                    match chosen {
+                        Some(LrAction::Reduce(_)) => keep_shift = false,
+                        Some(_) => dropped.push(rule_index),
+                        None => {
+                            keep_shift = false;
+                            dropped.push(rule_index);
+                        }
+                    }
+                    res.push(LrResolution {
+                        state: index,
+                        terminal,
+                        shift,
+                        rule_index,
+                        chosen,
+                        reason,
+                    });
+                }
+                actions.retain(|action| match action {
+                    LrAction::Shift(_) => keep_shift,
+                    LrAction::Reduce(r) => !dropped.contains(r),
+                    LrAction::Accept => true,
+                });
+            }
+        }
+        res.sort_by_key(|resolution| {
+            (resolution.state, resolution.terminal, resolution.rule_index)
+        });
+        self.resolutions = res;
+    }
+
+    // shift/reduce conflicts resolved while building the table
+    pub fn resolutions(&self) -> &[LrResolution<'a>] {
+        &self.resolutions
+    }
+}
+
+impl<'a> fmt::Display for LrResolution<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "conflict in state {} on {} between s{} and r{} resolved as ",
+            self.state, self.terminal, self.shift, self.rule_index
+        )?;
+        match &self.chosen {
+            Some(action) => write!(f, "{}", action)?,
+            None => write!(f, "error")?,
+        }
+        writeln!(f, " ({})", self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // flattened rules of code, which must have no errors
+    fn with_rules<T>(
+        code: &str,
+        f: impl for<'a> FnOnce(&'a File<'a>, &'a Vec<FlatRuleDef<'a>>) -> T,
+    ) -> T {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        f(file, &rules)
+    }
+
+    // rules[0] is the start rule, with a single alternative
+    const ASSIGN: &str = "S ::= A; A ::= L '=' R | R; L ::= '*' R | 'id'; R ::= L;";
+
+    #[test]
+    fn lr1_table_has_no_slr1_conflicts() {
+        with_rules(ASSIGN, |file, rules| {
+            let graph = lr_graph(rules);
+            assert!(!slr1_table(&graph, &file.precs).conflicts().is_empty());
+            let graph = lr1_graph(rules);
+            assert!(lr1_table(&graph, &file.precs).conflicts().is_empty());
+        });
+        with_rules(include_str!("../lr.ebnf"), |file, rules| {
+            let graph = lr1_graph(rules);
+            assert!(lr1_table(&graph, &file.precs).conflicts().is_empty());
+        });
+    }
+
+    #[test]
+    fn lalr1_table_keeps_lr0_states() {
+        with_rules(ASSIGN, |file, rules| {
+            let graph = lr_graph(rules);
+            assert!(lalr1_table(&graph, &file.precs).conflicts().is_empty());
+            assert!(lr1_graph(rules).states.len() > graph.states.len());
+        });
+        with_rules(include_str!("../lr.ebnf"), |file, rules| {
+            let graph = lr_graph(rules);
+            assert!(lalr1_table(&graph, &file.precs).conflicts().is_empty());
+        });
+    }
+
+    #[test]
+    fn conflicts_have_examples() {
+        with_rules("S ::= E; E ::= E '+' E | 'n';", |file, rules| {
+            let graph = lr_graph(rules);
+            let conflicts = lalr1_table(&graph, &file.precs).conflicts();
+            assert_eq!(conflicts.len(), 1);
+            let conflict = &conflicts[0];
+            assert_eq!(conflict.kind, ConflictKind::ShiftReduce);
+            assert_eq!(conflict.terminal, "'+'");
+            assert_eq!(conflict.items.len(), 2);
+            assert_eq!(conflict.prefix, vec!["'n'", "'+'", "'n'"]);
+            assert_eq!(conflict.continuation, vec!["'+'", "'n'"]);
+        });
+    }
+
+    #[test]
+    fn precedence_resolves_conflicts() {
+        with_rules(include_str!("../prec.ebnf"), |file, rules| {
+            let graph = lr_graph(rules);
+            for table in [
+                slr1_table(&graph, &file.precs),
+                lalr1_table(&graph, &file.precs),
+            ] {
+                assert!(table.conflicts().is_empty());
+                let reasons: BTreeSet<&str> =
+                    table.resolutions().iter().map(|r| r.reason).collect();
+                assert_eq!(
+                    reasons,
+                    vec![
+                        "left associative",
+                        "non associative",
+                        "right associative",
+                        "rule has higher precedence",
+                        "terminal has higher precedence",
+                    ]
+                    .into_iter()
+                    .collect()
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn lr_parse_builds_tree_or_reports_expected() {
+        with_rules(include_str!("../lr.ebnf"), |file, rules| {
+            let graph = lr_graph(rules);
+            let table = lalr1_table(&graph, &file.precs);
+            let tree = lr_parse(&table, &["'v'", "'+'", "'d'", "'*'", "'v'"]).unwrap();
+            assert_eq!(tree.name(), "S");
+            let err = lr_parse(&table, &["'v'", "'+'"]).unwrap_err();
+            assert_eq!(err.position, 2);
+            assert_eq!(err.found, "#");
+            assert_eq!(err.expected, vec!["'('", "'d'", "'v'"]);
+        });
+    }
+
+    #[test]
+    fn lr_trace_records_every_step() {
+        with_rules("S ::= E; E ::= 'n';", |file, rules| {
+            let graph = lr_graph(rules);
+            let table = lalr1_table(&graph, &file.precs);
+            let (steps, res) = lr_trace(&table, &["'n'"]);
+            assert!(res.is_ok());
+            let actions: Vec<String> = steps
+                .iter()
+                .map(|step| step.action.as_ref().unwrap().to_string())
+                .collect();
+            assert_eq!(actions, vec!["s1", "r1", "acc"]);
+            assert_eq!(steps[0].input, vec!["'n'", "#"]);
+            assert_eq!(steps[2].symbols, vec!["E"]);
+            let markdown = format_lr_trace(&steps, TraceFormat::Markdown);
+            assert!(markdown.starts_with("| Step | States | Symbols | Input | Action |\n"));
+            assert!(markdown.contains("| `1` | `0` |  | `'n' #` | `s1` |"));
+            let latex = format_lr_trace(&steps, TraceFormat::Latex);
+            assert!(latex.contains("3 & 0 2 & E & \\# & acc \\\\"));
+        });
+    }
+
+    #[test]
+    fn overlaps_are_warnings() {
+        with_rules("S ::= A; A ::= 'a' 'x' | [a-z] 'y';", |file, rules| {
+            let graph = lr_graph(rules);
+            let table = lalr1_table(&graph, &file.precs);
+            let conflicts = table.conflicts();
+            assert_eq!(conflicts.len(), 1);
+            assert!(conflicts[0].is_warning());
+            assert_eq!(conflicts[0].overlap, Some(("[a-z]", 'a')));
+            assert!(lr_parse(&table, &["'a'", "'x'"]).is_ok());
+            assert!(lr_parse(&table, &["'b'", "'y'"]).is_ok());
+        });
+    }
+}
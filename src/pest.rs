@@ -0,0 +1,254 @@
+// emitter of pest grammars, keeping the ebnf operators
+use crate::emit::*;
+use crate::front::complement_ranges;
+use crate::*;
+use std::collections::BTreeSet;
+
+// rules become variants of an enum, so besides pest's own rules rust keywords are avoided
+const BUILTIN: &[&str] = &[
+    "ANY",
+    "SOI",
+    "EOI",
+    "PUSH",
+    "POP",
+    "POP_ALL",
+    "PEEK",
+    "PEEK_ALL",
+    "DROP",
+    "WHITESPACE",
+    "COMMENT",
+    "NEWLINE",
+    "ASCII",
+    "ASCII_DIGIT",
+    "ASCII_NONZERO_DIGIT",
+    "ASCII_BIN_DIGIT",
+    "ASCII_OCT_DIGIT",
+    "ASCII_HEX_DIGIT",
+    "ASCII_ALPHA",
+    "ASCII_ALPHA_LOWER",
+    "ASCII_ALPHA_UPPER",
+    "ASCII_ALPHANUMERIC",
+];
+
+fn escape(ch: char, quote: char) -> String {
+    match ch {
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\\' => "\\\\".to_string(),
+        _ if ch == quote => format!("\\{}", ch),
+        // also the ends of complemented classes, which are not printable
+        _ if ch == ' ' || ch.is_ascii_graphic() => ch.to_string(),
+        _ => format!("\\u{{{:X}}}", ch as u32),
+    }
+}
+
+fn string(value: &str) -> String {
+    let body: String = value.chars().map(|ch| escape(ch, '"')).collect();
+    format!("\"{}\"", body)
+}
+
+fn char_lit(ch: char) -> String {
+    format!("'{}'", escape(ch, '\''))
+}
+
+// ranges as a choice, a negated class by the ranges of the other characters
+// a sequence would skip whitespace in between
+fn class_expr(class: &CharClass) -> String {
+    let ranges = if class.negated {
+        complement_ranges(class)
+    } else {
+        class.ranges.clone()
+    };
+    let alts: Vec<String> = ranges
+        .iter()
+        .map(|(lo, hi)| {
+            if lo == hi {
+                char_lit(*lo)
+            } else {
+                format!("{}..{}", char_lit(*lo), char_lit(*hi))
+            }
+        })
+        .collect();
+    match alts.len() {
+        0 => "!ANY".to_string(),
+        1 => alts[0].clone(),
+        _ => format!("({})", alts.join(" | ")),
+    }
+}
+
+// rules made of characters, which have no whitespace between them
+// they refer only to such rules, and have a class or no terminal at all
+fn char_rules<'a>(rules: &[(&'a str, Vec<&'a Prod<'a>>)]) -> BTreeSet<&'a str> {
+    let mut res = BTreeSet::new();
+    loop {
+        let mut changed = false;
+        for (name, alts) in rules.iter() {
+            if res.contains(name) {
+                continue;
+            }
+            let (mut class, mut literal, mut chars, mut others) = (false, false, false, false);
+            for alt in alts.iter() {
+                visit(alt, &mut |prod| match prod {
+                    Prod::Class(..) => class = true,
+                    Prod::Terminal(..) => literal = true,
+                    Prod::NonTerminal(other, _) if res.contains(other) => chars = true,
+                    Prod::NonTerminal(other, _) => others |= other != name,
+                    _ => {}
+                });
+            }
+            if !others && (class || (chars && !literal)) {
+                res.insert(*name);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    res
+}
+
+struct Pest<'a> {
+    names: Names<'a>,
+}
+
+impl<'a> Pest<'a> {
+    fn rule_name(&mut self, name: &'a str) -> String {
+        self.names.get(name, || identifier(name))
+    }
+
+    // operand of a postfix operator
+    fn atom(&mut self, prod: &'a Prod<'a>) -> String {
+        let res = self.seq(prod);
+        match prod {
+            _ if res.is_empty() => "\"\"".to_string(),
+            Prod::Terminal(..) | Prod::Class(..) | Prod::NonTerminal(..) | Prod::Alt(..) => res,
+            _ => format!("({})", res),
+        }
+    }
+
+    // empty for eps, which pest writes as ""
+    fn seq(&mut self, prod: &'a Prod<'a>) -> String {
+        match prod {
+            Prod::Concat(l, r, _) => match as_plus(prod) {
+                Some(p) => format!("{}+", self.atom(p)),
+                None => {
+                    let (l, r) = (self.seq(l), self.seq(r));
+                    match (l.is_empty(), r.is_empty()) {
+                        (true, _) => r,
+                        (_, true) => l,
+                        _ => format!("{} ~ {}", l, r),
+                    }
+                }
+            },
+            Prod::Terminal(lit, _) if lit.value.is_empty() => String::new(),
+            Prod::Terminal(lit, _) => string(lit.value),
+            Prod::Class(class, _) => class_expr(class),
+            Prod::NonTerminal(name, _) => self.rule_name(name),
+            Prod::Optional(p, _) => format!("{}?", self.atom(p)),
+            Prod::Star(p, _) => format!("{}*", self.atom(p)),
+            Prod::Repeat(p, min, Some(max), _) if min == max => {
+                format!("{}{{{}}}", self.atom(p), min)
+            }
+            Prod::Repeat(p, min, Some(max), _) => format!("{}{{{}, {}}}", self.atom(p), min, max),
+            Prod::Repeat(p, min, None, _) => format!("{}{{{},}}", self.atom(p), min),
+            Prod::Alt(alts, _) => format!("({})", self.alts(alts)),
+            Prod::Eps(_) => String::new(),
+        }
+    }
+
+    fn alts(&mut self, alts: &[&'a Prod<'a>]) -> String {
+        let alts: Vec<String> = alts.iter().map(|alt| self.atom_or_seq(alt)).collect();
+        alts.join(" | ")
+    }
+
+    fn atom_or_seq(&mut self, prod: &'a Prod<'a>) -> String {
+        let res = self.seq(prod);
+        if res.is_empty() {
+            "\"\"".to_string()
+        } else {
+            res
+        }
+    }
+}
+
+pub fn emit_pest(file: &File) -> String {
+    use std::fmt::Write;
+    let mut pest = Pest {
+        names: Names::new(&[BUILTIN, RUST_KEYWORDS].concat()),
+    };
+    let mut res = String::new();
+    res.push_str(
+        "// pest is a PEG: alternatives are tried in order and left recursion is not supported\n",
+    );
+    res.push_str("// rules of characters are atomic, but rules that also have literals, like\n");
+    res.push_str("// Digit+ '.' Digit+, skip whitespace between them unless made atomic with @\n");
+    res.push_str("WHITESPACE = _{ \" \" | \"\\t\" | \"\\r\" | \"\\n\" }\n");
+    let rules = grouped_rules(file);
+    let atomic = char_rules(&rules);
+    for (name, alts) in rules {
+        let modifier = if atomic.contains(name) { "@" } else { "" };
+        let _ = writeln!(res, "\n{} = {}{{", pest.rule_name(name), modifier);
+        for (i, alt) in alts.into_iter().enumerate() {
+            let sep = if i == 0 { " " } else { "|" };
+            let _ = writeln!(res, "  {} {}", sep, pest.atom_or_seq(alt));
+        }
+        res.push_str("}\n");
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(code: &str) -> String {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        emit_pest(file)
+    }
+
+    #[test]
+    fn pest_rules_of_characters_are_atomic() {
+        let code = "S ::= Num ('+' Num)* | Id;\nNum ::= Digit+ '.' Digit+;\nDigit ::= [0-9];\n\
+                    Id ::= [a-z] [a-z0-9]* | 'if' 'x'? | _;\n";
+        let expected = r#"// pest is a PEG: alternatives are tried in order and left recursion is not supported
+// rules of characters are atomic, but rules that also have literals, like
+// Digit+ '.' Digit+, skip whitespace between them unless made atomic with @
+WHITESPACE = _{ " " | "\t" | "\r" | "\n" }
+
+S = {
+    Num ~ ("+" ~ Num)*
+  | Id
+}
+
+Num = {
+    Digit+ ~ "." ~ Digit+
+}
+
+Digit = @{
+    '0'..'9'
+}
+
+Id = @{
+    'a'..'z' ~ ('a'..'z' | '0'..'9')*
+  | "if" ~ "x"?
+  | ""
+}
+"#;
+        assert_eq!(emitted(code), expected);
+    }
+
+    #[test]
+    fn pest_keeps_repetitions() {
+        let found = emitted("S ::= [^a-z] 'a'{1,2} 'b'{2,} 'c'{3} ('d' | 'e' 'f')?;");
+        let rule = r#"('\u{0}'..'`' | '{'..'\u{10FFFF}') ~ "a"{1, 2} ~ "b"{2,} ~ "c"{3} ~ ("d" | "e" ~ "f")?"#;
+        assert!(
+            found.ends_with(&format!("S = @{{\n    {}\n}}\n", rule)),
+            "{}",
+            found
+        );
+    }
+}
@@ -0,0 +1,252 @@
+// emitter of parsers in the #[lalr1] format of parser-macros, with empty actions
+use crate::emit::*;
+use crate::*;
+use std::collections::BTreeMap;
+
+// the lexer reserves names starting with _, like _Eps for skipped text
+const KEYWORDS: &[&str] = &["Parser", "Token", "TokenKind", "Lexer"];
+
+// a key of the lexical table, as a toml string
+fn toml_string(regex: &str) -> String {
+    if !regex.contains('\'') {
+        return format!("'{}'", regex);
+    }
+    let body = regex.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", body)
+}
+
+fn type_name(name: &str) -> String {
+    let res = camel_case(name);
+    if res.starts_with('_') {
+        format!("T{}", res)
+    } else {
+        res
+    }
+}
+
+// snake_case of a CamelCase name, for the action functions
+fn snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for (i, ch) in name.chars().enumerate() {
+        if ch.is_ascii_uppercase() && i > 0 {
+            res.push('_');
+        }
+        res.push(ch.to_ascii_lowercase());
+    }
+    res
+}
+
+struct Skeleton<'a> {
+    names: Names<'a>,
+    // regex and name of each token, in the order the lexer tries them
+    tokens: Vec<(String, String)>,
+}
+
+impl<'a> Skeleton<'a> {
+    // terminals with the same regex are the same token
+    fn token(&mut self, regex: String, want: impl FnOnce() -> String) -> String {
+        if let Some((_, name)) = self.tokens.iter().find(|(r, _)| *r == regex) {
+            return name.clone();
+        }
+        let name = self.names.fresh(want());
+        self.tokens.push((regex, name.clone()));
+        name
+    }
+}
+
+// literals are matched before classes, and whitespace is skipped
+// a class matching some literal is a non terminal, which also takes the literal's token
+pub fn emit_lalr1(file: &File, rules: &[FlatRuleDef]) -> String {
+    use std::fmt::Write;
+    let values = literal_values(file);
+    let mut skeleton = Skeleton {
+        names: Names::new(&[KEYWORDS, RUST_KEYWORDS].concat()),
+        tokens: vec![],
+    };
+    for rule in rules.iter() {
+        skeleton.names.get(rule.name, || type_name(rule.name));
+    }
+    let mut terminals = BTreeMap::new();
+    let mut classes = 0;
+    for (raw, value) in values.iter().filter(|(_, value)| !value.is_empty()) {
+        let name = skeleton.token(regex_literal(value), || {
+            type_name(&token_words(value).join("_"))
+        });
+        terminals.insert(raw.as_str(), name);
+    }
+    // non terminals of classes with the class token and the literal tokens
+    let mut chars: Vec<(String, String, Vec<String>)> = vec![];
+    for prod in rules.iter().flat_map(|rule| rule.prod.iter()) {
+        if let FlatProd::Class(class) = prod {
+            let name = skeleton.token(regex_class(class), || {
+                classes += 1;
+                format!("Class{}", classes - 1)
+            });
+            let literals: Vec<String> = class_literals(&values, class)
+                .into_iter()
+                .map(|value| terminals[terminal_name(value).as_str()].clone())
+                .collect();
+            let name = match chars.iter().find(|(_, token, _)| *token == name) {
+                Some((symbol, ..)) => symbol.clone(),
+                None if literals.is_empty() => name,
+                None => {
+                    let symbol = skeleton.names.fresh(format!("{}Char", name));
+                    chars.push((symbol.clone(), name, literals));
+                    symbol
+                }
+            };
+            terminals.insert(class.raw, name);
+        }
+    }
+
+    let mut res = String::new();
+    res.push_str("// needs #![feature(proc_macro_hygiene)] in the crate root\n");
+    res.push_str("use parser_macros::lalr1;\n\n");
+    res.push_str("pub struct Parser<'p>(std::marker::PhantomData<&'p ()>);\n\n");
+    if let Some(start) = rules.first() {
+        let _ = writeln!(
+            res,
+            "#[lalr1({})]",
+            skeleton.names.get(start.name, || unreachable!())
+        );
+    }
+    res.push_str("#[lex(\n    r##\"\n");
+    if !file.precs.is_empty() {
+        res.push_str("priority = [\n");
+        for prec in file.precs.iter() {
+            let assoc = match prec.assoc {
+                Assoc::Left => "left",
+                Assoc::Right => "right",
+                Assoc::NonAssoc => "no_assoc",
+            };
+            let terms: Vec<String> = prec
+                .terms
+                .iter()
+                // terminals declared but unused are not in the grammar
+                .map(|term| {
+                    let value = terminal_value(term);
+                    skeleton.token(regex_literal(&value), || {
+                        type_name(&token_words(&value).join("_"))
+                    })
+                })
+                .map(|name| format!("'{}'", name))
+                .collect();
+            let _ = writeln!(
+                res,
+                "    {{ assoc = '{}', terms = [{}] }},",
+                assoc,
+                terms.join(", ")
+            );
+        }
+        res.push_str("]\n\n");
+    }
+    res.push_str("[lexical]\n");
+    for (regex, name) in skeleton.tokens.iter() {
+        let _ = writeln!(res, "{} = '{}'", toml_string(regex), name);
+    }
+    res.push_str("'\\s+' = '_Eps'\n\"##\n)]\n");
+
+    res.push_str("impl<'p> Parser<'p> {\n");
+    let mut fns = Names::new(RUST_KEYWORDS);
+    for (i, rule) in rules.iter().enumerate() {
+        let lhs = skeleton.names.get(rule.name, || unreachable!());
+        let mut symbols = vec![];
+        let mut params = vec!["&self".to_string()];
+        for prod in rule.prod.iter() {
+            let (symbol, ty) = match prod {
+                FlatProd::Eps => continue,
+                FlatProd::NonTerminal(name) => (skeleton.names.get(name, || unreachable!()), "()"),
+                // empty literals match nothing
+                FlatProd::Terminal(raw) => match terminals.get(raw) {
+                    Some(name) => (name.clone(), "Token<'p>"),
+                    None => continue,
+                },
+                FlatProd::Class(class) => (terminals[class.raw].clone(), "Token<'p>"),
+            };
+            params.push(format!("_{}: {}", symbols.len(), ty));
+            symbols.push(symbol);
+        }
+        let name = fns.fresh(format!("{}_{}", snake_case(&lhs), i));
+        if i > 0 {
+            res.push('\n');
+        }
+        let _ = writeln!(res, "    #[rule({} -> {})]", lhs, symbols.join(" "));
+        let _ = writeln!(res, "    fn {}({}) {{}}", name, params.join(", "));
+    }
+    for (symbol, token, literals) in chars.iter() {
+        let name = fns.fresh(snake_case(symbol));
+        for (i, token) in std::iter::once(token).chain(literals.iter()).enumerate() {
+            let _ = writeln!(res, "\n    #[rule({} -> {})]", symbol, token);
+            let _ = writeln!(
+                res,
+                "    fn {}_{}(&self, t: Token<'p>) -> Token<'p> {{ t }}",
+                name, i
+            );
+        }
+    }
+    res.push_str("}\n");
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(code: &str) -> String {
+        let alloc = ASTAlloc::default();
+        let (file, errors) = work(code, &alloc);
+        assert!(!errors.has_error(), "{:?}", errors);
+        let flatten_alloc = FlattenAlloc::default();
+        let rules = flatten(file, &flatten_alloc);
+        emit_lalr1(file, &rules)
+    }
+
+    #[test]
+    fn skeleton_lexes_literals_before_classes() {
+        let code = "%left '+';\nS ::= E;\nE ::= E '+' E | [a-z] | 'x' | 'fn';\n";
+        let expected = r####"// needs #![feature(proc_macro_hygiene)] in the crate root
+use parser_macros::lalr1;
+
+pub struct Parser<'p>(std::marker::PhantomData<&'p ()>);
+
+#[lalr1(S)]
+#[lex(
+    r##"
+priority = [
+    { assoc = 'left', terms = ['Plus'] },
+]
+
+[lexical]
+'\+' = 'Plus'
+'fn' = 'Fn'
+'x' = 'X'
+'[a-z]' = 'Class0'
+'\s+' = '_Eps'
+"##
+)]
+impl<'p> Parser<'p> {
+    #[rule(S -> E)]
+    fn s_0(&self, _0: ()) {}
+
+    #[rule(E -> Fn)]
+    fn e_1(&self, _0: Token<'p>) {}
+
+    #[rule(E -> X)]
+    fn e_2(&self, _0: Token<'p>) {}
+
+    #[rule(E -> Class0Char)]
+    fn e_3(&self, _0: Token<'p>) {}
+
+    #[rule(E -> E Plus E)]
+    fn e_4(&self, _0: (), _1: Token<'p>, _2: ()) {}
+
+    #[rule(Class0Char -> Class0)]
+    fn class0_char_0(&self, t: Token<'p>) -> Token<'p> { t }
+
+    #[rule(Class0Char -> X)]
+    fn class0_char_1(&self, t: Token<'p>) -> Token<'p> { t }
+}
+"####;
+        assert_eq!(emitted(code), expected);
+    }
+}
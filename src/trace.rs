@@ -0,0 +1,82 @@
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Plain,
+    Markdown,
+    Latex,
+}
+
+impl TraceFormat {
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name {
+            "plain" => Some(TraceFormat::Plain),
+            "markdown" => Some(TraceFormat::Markdown),
+            "latex" => Some(TraceFormat::Latex),
+            _ => None,
+        }
+    }
+}
+
+fn escape(cell: &str, format: TraceFormat) -> String {
+    match format {
+        TraceFormat::Plain => cell.to_string(),
+        TraceFormat::Markdown => {
+            if cell.is_empty() {
+                String::new()
+            } else {
+                format!("`{}`", cell.replace('|', "\\|"))
+            }
+        }
+        TraceFormat::Latex => {
+            let mut res = String::new();
+            for ch in cell.chars() {
+                match ch {
+                    '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                        res.push('\\');
+                        res.push(ch);
+                    }
+                    '\\' => res.push_str("\\textbackslash{}"),
+                    '^' => res.push_str("\\textasciicircum{}"),
+                    '~' => res.push_str("\\textasciitilde{}"),
+                    _ => res.push(ch),
+                }
+            }
+            res
+        }
+    }
+}
+
+// print a table of parse steps
+pub fn format_trace(header: &[&str], rows: &[Vec<String>], format: TraceFormat) -> String {
+    let mut res = String::new();
+    match format {
+        TraceFormat::Plain => {
+            writeln!(res, "{}", header.join("\t")).unwrap();
+            for row in rows.iter() {
+                writeln!(res, "{}", row.join("\t")).unwrap();
+            }
+        }
+        TraceFormat::Markdown => {
+            writeln!(res, "| {} |", header.join(" | ")).unwrap();
+            writeln!(res, "|{}", " --- |".repeat(header.len())).unwrap();
+            for row in rows.iter() {
+                let cells: Vec<String> = row.iter().map(|cell| escape(cell, format)).collect();
+                writeln!(res, "| {} |", cells.join(" | ")).unwrap();
+            }
+        }
+        TraceFormat::Latex => {
+            writeln!(res, "\\begin{{tabular}}{{|{}}}", "l|".repeat(header.len())).unwrap();
+            writeln!(res, "\\hline").unwrap();
+            writeln!(res, "{} \\\\", header.join(" & ")).unwrap();
+            writeln!(res, "\\hline").unwrap();
+            for row in rows.iter() {
+                let cells: Vec<String> = row.iter().map(|cell| escape(cell, format)).collect();
+                writeln!(res, "{} \\\\", cells.join(" & ")).unwrap();
+            }
+            writeln!(res, "\\hline").unwrap();
+            writeln!(res, "\\end{{tabular}}").unwrap();
+        }
+    }
+    res
+}
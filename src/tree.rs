@@ -0,0 +1,148 @@
+use crate::{terminal_name, terminal_value, CharClass, FlatProd, FlatRuleDef};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// parse tree built by lr and ll parser runtimes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTree<'a> {
+    Terminal(&'a str),
+    NonTerminal {
+        name: &'a str,
+        // index into flattened rules
+        rule_index: usize,
+        children: Vec<ParseTree<'a>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError<'a> {
+    // index of the offending token in input
+    pub position: usize,
+    // "#" at the end of input
+    pub found: &'a str,
+    pub expected: Vec<&'a str>,
+}
+
+// split sentence into terminals named like flattened rules name them
+// quoted words have their escapes resolved, bare words are taken as they are
+pub fn split_terminals(sentence: &str) -> Vec<String> {
+    sentence
+        .split_whitespace()
+        .map(|word| {
+            let quoted = word.len() >= 2
+                && ((word.starts_with('\'') && word.ends_with('\''))
+                    || (word.starts_with('"') && word.ends_with('"')));
+            if quoted {
+                terminal_name(&terminal_value(word))
+            } else {
+                terminal_name(word)
+            }
+        })
+        .collect()
+}
+
+// character classes of the rules by name
+pub type Classes<'a> = BTreeMap<&'a str, &'a CharClass<'a>>;
+
+pub fn classes<'a>(rules: &[FlatRuleDef<'a>]) -> Classes<'a> {
+    let mut res = BTreeMap::new();
+    for prod in rules.iter().flat_map(|rule| rule.prod.iter()) {
+        if let FlatProd::Class(class) = prod {
+            res.insert(class.raw, *class);
+        }
+    }
+    res
+}
+
+// the character a terminal of one character stands for
+pub fn single_char(terminal: &str) -> Option<char> {
+    if terminal.len() < 2 || !terminal.starts_with('\'') {
+        return None;
+    }
+    let value = terminal_value(terminal);
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None,
+    }
+}
+
+// a character matched by both terminals, when one of them is a class
+pub fn terminal_overlap(classes: &Classes, a: &str, b: &str) -> Option<char> {
+    match (classes.get(a), classes.get(b)) {
+        (Some(a), Some(b)) => a.overlap(b),
+        (Some(class), None) => single_char(b).filter(|ch| class.contains(*ch)),
+        (None, Some(class)) => single_char(a).filter(|ch| class.contains(*ch)),
+        (None, None) => None,
+    }
+}
+
+// terminal of the grammar an input terminal is taken as, among those accepted
+// itself, or else the first class containing its character
+pub fn matching_terminal<'a>(
+    classes: &Classes<'a>,
+    terminal: &'a str,
+    accepted: impl Fn(&str) -> bool,
+) -> &'a str {
+    if accepted(terminal) {
+        return terminal;
+    }
+    single_char(terminal)
+        .and_then(|ch| {
+            classes
+                .iter()
+                .find(|(name, class)| class.contains(ch) && accepted(name))
+        })
+        .map_or(terminal, |(name, _)| *name)
+}
+
+impl<'a> ParseTree<'a> {
+    pub fn name(&self) -> &'a str {
+        match self {
+            ParseTree::Terminal(name) => name,
+            ParseTree::NonTerminal { name, .. } => name,
+        }
+    }
+
+    fn fmt_level(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        match self {
+            ParseTree::Terminal(name) => writeln!(f, "{:indent$}{}", "", name, indent = level * 2),
+            ParseTree::NonTerminal {
+                name,
+                rule_index,
+                children,
+            } => {
+                writeln!(
+                    f,
+                    "{:indent$}{} (r{})",
+                    "",
+                    name,
+                    rule_index,
+                    indent = level * 2
+                )?;
+                for child in children.iter() {
+                    child.fmt_level(f, level + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for ParseTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_level(f, 0)
+    }
+}
+
+impl<'a> fmt::Display for ParseError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syntax error at token {}: found {}, expected {}",
+            self.position,
+            self.found,
+            self.expected.join(" ")
+        )
+    }
+}
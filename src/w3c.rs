@@ -0,0 +1,348 @@
+// front-end for the ebnf notation of W3C specifications like XML and XPath
+use crate::front::*;
+use crate::*;
+
+struct W3cParser<'a> {
+    s: Scanner<'a>,
+    alloc: &'a ASTAlloc<'a>,
+    errors: Errors,
+    // names of rules left out because of syntax errors after their name
+    broken: Vec<&'a str>,
+    // rules of the previous pass, to resolve exceptions
+    defs: Defs<'a>,
+    has_exception: bool,
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+// [ wfc: ... ] and [ vc: ... ] constraints annotating a rule
+fn is_constraint(rest: &str) -> bool {
+    if !rest.starts_with('[') {
+        return false;
+    }
+    let rest = rest[1..].trim_start();
+    let word: String = rest.chars().take_while(char::is_ascii_alphabetic).collect();
+    let word = word.to_ascii_lowercase();
+    (word == "wfc" || word == "vc") && rest[word.len()..].trim_start().starts_with(':')
+}
+
+impl<'a> W3cParser<'a> {
+    // skip spaces, comments and constraints
+    fn gap(&mut self) {
+        loop {
+            self.s.take_while(char::is_whitespace);
+            let lo = self.s.pos;
+            if self.s.eat("/*") {
+                while !self.s.eat("*/") {
+                    if self.s.bump().is_none() {
+                        return self
+                            .errors
+                            .issue(self.s.loc(lo), ErrorKind::UnterminatedComment);
+                    }
+                }
+            } else if is_constraint(self.s.rest()) {
+                self.s.take_while(|ch| ch != ']');
+                self.s.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // whether a rule, possibly numbered like [1], starts at the cursor
+    fn at_rule(&self) -> bool {
+        let mut rest = self.s.rest();
+        if rest.starts_with('[') {
+            let label: &str = rest[1..].split(']').next().unwrap();
+            if !label.starts_with(|ch: char| ch.is_ascii_digit())
+                || !label.chars().all(|ch| ch.is_ascii_alphanumeric())
+                || label.len() + 1 == rest.len()
+            {
+                return false;
+            }
+            rest = rest[label.len() + 2..].trim_start();
+        }
+        let name = rest.trim_start_matches(is_name_char);
+        name.len() < rest.len()
+            && rest.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+            && name.trim_start().starts_with("::=")
+    }
+
+    fn name(&mut self) -> Option<&'a str> {
+        if !self
+            .s
+            .peek()
+            .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        {
+            self.s.error(&mut self.errors, &["symbol"]);
+            return None;
+        }
+        Some(self.s.take_while(is_name_char))
+    }
+
+    fn rule(&mut self) -> Option<RuleDef<'a>> {
+        let lo = self.s.pos;
+        if self.at_rule() && self.s.eat("[") {
+            // skip the number
+            self.s.take_while(|ch| ch != ']');
+            self.s.bump();
+            self.s.take_while(char::is_whitespace);
+        }
+        let name = self.name()?;
+        // popped once the rule parses
+        self.broken.push(name);
+        self.gap();
+        if !self.s.eat("::=") {
+            self.s.error(&mut self.errors, &["`::=`"]);
+            return None;
+        }
+        let prod = self.alt()?;
+        self.broken.pop();
+        Some(RuleDef {
+            name,
+            span: self.s.span(lo, prod.span().hi.max(lo)),
+            // alternatives of the rule itself, like the other dialects have them
+            prod: match prod {
+                Prod::Alt(alts, _) => alts.clone(),
+                _ => vec![prod],
+            },
+        })
+    }
+
+    // sequences separated by |
+    fn alt(&mut self) -> Option<&'a Prod<'a>> {
+        let lo = self.s.pos;
+        let mut alts = vec![self.seq()?];
+        while self.s.eat("|") {
+            alts.push(self.seq()?);
+        }
+        let hi = alts.last().unwrap().span().hi.max(lo);
+        let span = self.s.span(lo, hi);
+        Some(make_alt(self.alloc, alts, span))
+    }
+
+    fn seq(&mut self) -> Option<&'a Prod<'a>> {
+        self.gap();
+        let lo = self.s.pos;
+        let mut items = vec![];
+        loop {
+            self.gap();
+            match self.s.peek() {
+                None | Some('|') | Some(')') => break,
+                _ if self.at_rule() => break,
+                _ => items.push(self.diff()?),
+            }
+        }
+        Some(make_concat(self.alloc, items, self.s.span(lo, lo)))
+    }
+
+    // item with an optional exception
+    fn diff(&mut self) -> Option<&'a Prod<'a>> {
+        let lo = self.s.pos;
+        let item = self.item()?;
+        self.gap();
+        if !self.s.eat("-") {
+            return Some(item);
+        }
+        self.gap();
+        let except = self.item()?;
+        self.has_exception = true;
+        let span = self.s.span(lo, self.s.pos);
+        let raw = &self.s.code[lo..self.s.pos];
+        Some(make_exception(
+            self.alloc,
+            &mut self.errors,
+            &self.defs,
+            (item, except),
+            raw,
+            span,
+        ))
+    }
+
+    // primary with postfix operators
+    fn item(&mut self) -> Option<&'a Prod<'a>> {
+        let lo = self.s.pos;
+        let mut p = self.primary()?;
+        loop {
+            let op = self.s.peek();
+            if !(op == Some('?') || op == Some('*') || op == Some('+')) {
+                return Some(p);
+            }
+            self.s.bump();
+            let span = self.s.span(lo, self.s.pos);
+            p = match op {
+                Some('?') => self.alloc.prod.alloc(Prod::Optional(p, span)),
+                Some('*') => self.alloc.prod.alloc(Prod::Star(p, span)),
+                _ => {
+                    let star = self.alloc.prod.alloc(Prod::Star(p, span));
+                    self.alloc.prod.alloc(Prod::Concat(p, star, span))
+                }
+            };
+        }
+    }
+
+    // #xN character
+    fn hex_char(&mut self) -> Option<char> {
+        let digits = self.s.take_while(|ch| ch.is_ascii_hexdigit());
+        let ch = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(std::char::from_u32);
+        if ch.is_none() {
+            self.s.error(&mut self.errors, &["hexadecimal code point"]);
+        }
+        ch
+    }
+
+    // character inside a class
+    fn class_char(&mut self) -> Option<char> {
+        if self.s.eat("#x") {
+            return self.hex_char();
+        }
+        match self.s.bump() {
+            Some(ch) => Some(ch),
+            None => {
+                self.s.error(&mut self.errors, &["`]`"]);
+                None
+            }
+        }
+    }
+
+    fn class(&mut self) -> Option<CharClass<'a>> {
+        let lo = self.s.pos;
+        self.s.eat("[");
+        let negated = self.s.eat("^");
+        let mut ranges = vec![];
+        while !self.s.eat("]") {
+            let lo = self.class_char()?;
+            // a-b is a range, while a trailing - is itself
+            let rest = self.s.rest();
+            if rest.starts_with('-') && !rest.starts_with("-]") {
+                self.s.bump();
+                let hi = self.class_char()?;
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+        Some(CharClass {
+            raw: &self.s.code[lo..self.s.pos],
+            negated,
+            ranges,
+        })
+    }
+
+    fn primary(&mut self) -> Option<&'a Prod<'a>> {
+        let lo = self.s.pos;
+        match self.s.peek() {
+            Some('(') => {
+                self.s.bump();
+                let p = self.alt()?;
+                self.gap();
+                if !self.s.eat(")") {
+                    self.s.error(&mut self.errors, &["`)`", "`|`"]);
+                    return None;
+                }
+                Some(p)
+            }
+            Some('[') => {
+                let class = self.class()?;
+                let span = self.s.span(lo, self.s.pos);
+                Some(self.alloc.prod.alloc(Prod::Class(class, span)))
+            }
+            Some('#') if self.s.eat("#x") => {
+                let ch = self.hex_char()?;
+                let raw = &self.s.code[lo..self.s.pos];
+                let value = self.alloc.string.alloc(ch.to_string());
+                let span = self.s.span(lo, self.s.pos);
+                Some(
+                    self.alloc
+                        .prod
+                        .alloc(Prod::Terminal(Literal { raw, value }, span)),
+                )
+            }
+            Some(quote @ '\'') | Some(quote @ '"') => {
+                self.s.bump();
+                self.s.take_while(|ch| ch != quote);
+                if self.s.bump().is_none() {
+                    return self
+                        .errors
+                        .issue(self.s.loc(lo), ErrorKind::UnrecognizedChar(quote));
+                }
+                let raw = &self.s.code[lo..self.s.pos];
+                let value = &raw[1..raw.len() - 1];
+                let span = self.s.span(lo, self.s.pos);
+                Some(
+                    self.alloc
+                        .prod
+                        .alloc(Prod::Terminal(Literal { raw, value }, span)),
+                )
+            }
+            Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+                let name = self.name()?;
+                let span = self.s.span(lo, self.s.pos);
+                Some(self.alloc.prod.alloc(Prod::NonTerminal(name, span)))
+            }
+            _ => {
+                self.s.error(
+                    &mut self.errors,
+                    &["symbol", "string", "`#x`", "`[`", "`(`"],
+                );
+                None
+            }
+        }
+    }
+
+    // skip to the start of the next rule
+    fn recover(&mut self) {
+        loop {
+            self.gap();
+            match self.s.peek() {
+                None => return,
+                _ if self.at_rule() => return,
+                Some(quote @ '\'') | Some(quote @ '"') => {
+                    self.s.bump();
+                    self.s.take_while(|ch| ch != quote);
+                    self.s.bump();
+                }
+                _ => {
+                    self.s.bump();
+                }
+            }
+        }
+    }
+
+    fn file(&mut self) -> Vec<RuleDef<'a>> {
+        let mut rules = vec![];
+        loop {
+            self.gap();
+            if self.s.peek().is_none() {
+                break;
+            }
+            match self.rule() {
+                Some(rule) => rules.push(rule),
+                None => self.recover(),
+            }
+        }
+        if rules.is_empty() && self.errors.0.is_empty() {
+            self.s.error(&mut self.errors, &["symbol"]);
+        }
+        rules
+    }
+}
+
+pub fn parse_w3c<'a>(code: &'a str, alloc: &'a ASTAlloc<'a>) -> (&'a File<'a>, Errors) {
+    resolve_exceptions(alloc, |defs| {
+        let mut parser = W3cParser {
+            s: Scanner::new(code),
+            alloc,
+            errors: Errors::default(),
+            broken: vec![],
+            defs,
+            has_exception: false,
+        };
+        let rules = parser.file();
+        (rules, parser.broken, parser.errors, parser.has_exception)
+    })
+}